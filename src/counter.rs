@@ -0,0 +1,293 @@
+//! Counting of the arithmetic operations performed on tracked values
+//!
+//! The counter is global: operations performed on any thread are added to the same totals, so
+//! parallel computations are accounted for. Each count is updated with relaxed atomic operations,
+//! which never lose increments but don't order them with anything else. In particular, counts
+//! read while other threads are still working may not include their latest operations, and the
+//! different kinds may be read at slightly different moments. Once those threads have been
+//! joined, all their operations are visible.
+
+use alloc::format;
+#[cfg(feature = "std")]
+use core::cell::Cell;
+use core::{
+    fmt::{self, Display},
+    ops::Sub,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Number of operations of each kind performed so far
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpCounts {
+    pub add: u64,
+    pub sub: u64,
+    pub mul: u64,
+    pub neg: u64,
+    pub invert: u64,
+}
+
+impl OpCounts {
+    /// The number of operations of one kind
+    pub fn get(&self, op: Op) -> u64 {
+        match op {
+            Op::Add => self.add,
+            Op::Sub => self.sub,
+            Op::Mul => self.mul,
+            Op::Neg => self.neg,
+            Op::Invert => self.invert,
+        }
+    }
+
+    /// Compares these counts, e.g. of a reference computation, with the counts of another one
+    pub fn diff(&self, other: &OpCounts) -> OpCountsDiff {
+        OpCountsDiff {
+            before: *self,
+            after: *other,
+        }
+    }
+}
+
+impl Sub for OpCounts {
+    type Output = OpCounts;
+
+    fn sub(self, rhs: OpCounts) -> OpCounts {
+        OpCounts {
+            add: self.add.saturating_sub(rhs.add),
+            sub: self.sub.saturating_sub(rhs.sub),
+            mul: self.mul.saturating_sub(rhs.mul),
+            neg: self.neg.saturating_sub(rhs.neg),
+            invert: self.invert.saturating_sub(rhs.invert),
+        }
+    }
+}
+
+/// Two sets of counts, displayed as a table of the change of each kind of operation
+///
+/// ```text
+/// op      before   after   delta
+/// mul         10       7      -3
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpCountsDiff {
+    pub before: OpCounts,
+    pub after: OpCounts,
+}
+
+impl OpCountsDiff {
+    /// How many more operations of this kind the second computation performed, negative if it
+    /// saved some
+    pub fn delta(&self, op: Op) -> i64 {
+        self.after.get(op) as i64 - self.before.get(op) as i64
+    }
+}
+
+impl Display for OpCountsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:<6}{:>8}{:>8}{:>8}", "op", "before", "after", "delta")?;
+        for op in Op::ALL {
+            let delta = match self.delta(op) {
+                0 => "0".into(),
+                d => format!("{d:+}"),
+            };
+            write!(
+                f,
+                "\n{:<6}{:>8}{:>8}{delta:>8}",
+                op.name(),
+                self.before.get(op),
+                self.after.get(op)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A kind of operation on tracked values
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Neg,
+    Invert,
+}
+
+static ADD: AtomicU64 = AtomicU64::new(0);
+static SUB: AtomicU64 = AtomicU64::new(0);
+static MUL: AtomicU64 = AtomicU64::new(0);
+static NEG: AtomicU64 = AtomicU64::new(0);
+static INVERT: AtomicU64 = AtomicU64::new(0);
+
+impl Op {
+    const ALL: [Op; 5] = [Op::Add, Op::Sub, Op::Mul, Op::Neg, Op::Invert];
+
+    fn name(self) -> &'static str {
+        match self {
+            Op::Add => "add",
+            Op::Sub => "sub",
+            Op::Mul => "mul",
+            Op::Neg => "neg",
+            Op::Invert => "invert",
+        }
+    }
+
+    fn counter(self) -> &'static AtomicU64 {
+        match self {
+            Op::Add => &ADD,
+            Op::Sub => &SUB,
+            Op::Mul => &MUL,
+            Op::Neg => &NEG,
+            Op::Invert => &INVERT,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    /// Number of live [`OpPause`] guards on this thread
+    static PAUSES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Number of live [`OpPause`] guards, on any thread since there are no thread-locals
+#[cfg(not(feature = "std"))]
+static PAUSES: AtomicU64 = AtomicU64::new(0);
+
+fn paused() -> bool {
+    #[cfg(feature = "std")]
+    return PAUSES.with(Cell::get) > 0;
+    #[cfg(not(feature = "std"))]
+    return PAUSES.load(Ordering::Relaxed) > 0;
+}
+
+pub(crate) fn record(op: Op) {
+    if !paused() {
+        op.counter().fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Access to the global operation counter
+pub struct OpCounter;
+
+impl OpCounter {
+    /// The operations counted, on all threads, since the start or the last [`OpCounter::reset`]
+    pub fn snapshot() -> OpCounts {
+        let load = |op: Op| op.counter().load(Ordering::Relaxed);
+        OpCounts {
+            add: load(Op::Add),
+            sub: load(Op::Sub),
+            mul: load(Op::Mul),
+            neg: load(Op::Neg),
+            invert: load(Op::Invert),
+        }
+    }
+
+    /// Sets all the counts back to zero
+    pub fn reset() {
+        for op in Op::ALL {
+            op.counter().store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Starts measuring a region; the guard reports the operations performed while it is alive
+    ///
+    /// Scopes can be nested freely, each one only sees the operations done since it was created.
+    pub fn scope() -> OpScope {
+        OpScope {
+            start: Self::snapshot(),
+            label: None,
+        }
+    }
+
+    /// Like [`OpCounter::scope`], but the guard prints `label: ` and its delta to stderr when
+    /// dropped
+    pub fn scope_labeled(label: &'static str) -> OpScope {
+        OpScope {
+            label: Some(label),
+            ..Self::scope()
+        }
+    }
+
+    /// Stops counting until the guard is dropped, e.g. to leave the setup of a measurement out
+    ///
+    /// The pause belongs to the current thread: operations on other threads are still counted.
+    /// Without the `std` feature there are no thread-locals, and the pause is global instead.
+    /// Pauses can be nested, counting resumes when the last guard is dropped.
+    pub fn pause() -> OpPause {
+        #[cfg(feature = "std")]
+        PAUSES.with(|p| p.set(p.get() + 1));
+        #[cfg(not(feature = "std"))]
+        PAUSES.fetch_add(1, Ordering::Relaxed);
+        OpPause { _private: () }
+    }
+}
+
+/// Guard returned by [`OpCounter::pause`], resuming the counting when dropped
+pub struct OpPause {
+    _private: (),
+}
+
+impl Drop for OpPause {
+    fn drop(&mut self) {
+        #[cfg(feature = "std")]
+        PAUSES.with(|p| p.set(p.get() - 1));
+        #[cfg(not(feature = "std"))]
+        PAUSES.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Guard returned by [`OpCounter::scope`], whose delta is read with [`OpScope::delta`]
+///
+/// A scope made with [`OpCounter::scope_labeled`] also prints its delta to stderr when dropped,
+/// unless the `std` feature is disabled.
+pub struct OpScope {
+    start: OpCounts,
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    label: Option<&'static str>,
+}
+
+impl OpScope {
+    /// The operations performed since this scope was created
+    pub fn delta(&self) -> OpCounts {
+        OpCounter::snapshot() - self.start
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for OpScope {
+    fn drop(&mut self) {
+        if let Some(label) = self.label {
+            eprintln!("{label}: {:?}", self.delta());
+        }
+    }
+}
+
+#[test]
+fn test_diff() {
+    let reference = OpCounts {
+        add: 4,
+        mul: 10,
+        invert: 1,
+        ..Default::default()
+    };
+    let optimized = OpCounts {
+        add: 5,
+        mul: 7,
+        invert: 1,
+        ..Default::default()
+    };
+    let diff = reference.diff(&optimized);
+
+    assert_eq!(diff.delta(Op::Mul), -3);
+    assert_eq!(diff.delta(Op::Add), 1);
+    assert_eq!(diff.delta(Op::Invert), 0);
+    assert_eq!(
+        diff.to_string(),
+        "op      before   after   delta\n\
+         add          4       5      +1\n\
+         sub          0       0       0\n\
+         mul         10       7      -3\n\
+         neg          0       0       0\n\
+         invert       1       1       0"
+    );
+}
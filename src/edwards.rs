@@ -0,0 +1,88 @@
+//! Debugging utilities for [curve25519_dalek::edwards]
+
+use alloc::boxed::Box;
+use core::{
+    fmt::{self, Debug},
+    ops::Neg,
+};
+
+use curve25519_dalek::{edwards::EdwardsPoint, scalar::Scalar as DalekScalar};
+
+use crate::{
+    counter::Op,
+    expr::Tree,
+    montgomery::TestMontgomeryPoint,
+    scalar::TestScalar,
+    tracked::{record_op, Encoding, MulTree, Tracked},
+};
+
+pub type TestEdwardsPoint = Tracked<EdwardsPoint>;
+
+impl MulTree<EdwardsPoint> for DalekScalar {
+    fn mul_tree(scalar: Tree, point: Tree) -> Tree {
+        Tree::scalar_mul_of(scalar, point)
+    }
+}
+
+impl Encoding for EdwardsPoint {
+    fn encoding(&self) -> [u8; 32] {
+        self.compress().to_bytes()
+    }
+}
+
+impl MulTree<DalekScalar> for EdwardsPoint {
+    fn mul_tree(point: Tree, scalar: Tree) -> Tree {
+        Tree::scalar_mul_of(scalar, point)
+    }
+}
+
+define_neg_variants!(Type = TestEdwardsPoint);
+
+impl Debug for TestEdwardsPoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("EdwardsPoint").field(&self.tree).finish()
+    }
+}
+
+impl TestEdwardsPoint {
+    /// Multiplies the basepoint by `scalar`, recorded as `scalar · B` like
+    /// [`RistrettoPoint::mul_base`](crate::ristretto::RistrettoPoint::mul_base)
+    pub fn mul_base(scalar: &TestScalar) -> Self {
+        let product = Self {
+            value: EdwardsPoint::mul_base(&scalar.value),
+            tree: Tree::scalar_mul_of(scalar.tree.clone(), Tree::Basepoint),
+        };
+        record_op(Op::Mul, &[&scalar.tree, &Tree::Basepoint], &product);
+        product
+    }
+
+    /// Converts the point to its Montgomery u-coordinate, recording the change of model
+    ///
+    /// Ristretto points have no such conversion in dalek, since their Edwards representative
+    /// isn't exposed.
+    pub fn to_montgomery(&self) -> TestMontgomeryPoint {
+        Tracked {
+            value: self.value.to_montgomery(),
+            tree: Tree::Convert("montgomery".into(), Box::new(self.tree.clone())),
+        }
+    }
+}
+
+#[test]
+fn test_to_montgomery() {
+    use crate::Named;
+
+    let a = TestScalar::from(2u8).named("a");
+    let p = TestEdwardsPoint::mul_base(&a).named("P");
+    let u = p.to_montgomery();
+    assert_eq!(u.value, p.value.to_montgomery());
+    assert_eq!(format!("{u:?}"), "MontgomeryPoint(montgomery(P))");
+
+    let back = u.to_edwards(0).unwrap();
+    assert_eq!(back.value, u.value.to_edwards(0).unwrap());
+    assert_eq!(format!("{back:?}"), "EdwardsPoint(edwards(montgomery(P)))");
+    assert_eq!(
+        format!("{:?}", (&a * &p).to_montgomery()),
+        "MontgomeryPoint(montgomery(a · P))"
+    );
+}
@@ -1,30 +1,666 @@
-use std::fmt::{self, Debug};
+//! Expression trees recording how tracked values were computed
 
-#[derive(Clone)]
+use alloc::{boxed::Box, collections::BTreeSet, format, string::String, vec, vec::Vec};
+#[cfg(feature = "digest")]
+use core::any::type_name;
+#[cfg(feature = "std")]
+use core::cell::Cell;
+use core::fmt::{self, Debug};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+use crate::hex;
+
+mod art;
+pub mod build;
+mod circuit;
+mod compare;
+mod cost;
+mod eval;
+mod lets;
+mod linear;
+mod minimal;
+mod simplify;
+mod smt;
+mod summary;
+mod sympy;
+mod wrap;
+
+pub use circuit::{Gate, Wire};
+pub use cost::{cheaper_of, CostModel};
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Tree {
     Zero,
     One,
     Unnamed,
+    /// The identity point
+    Identity,
+    /// The Ristretto basepoint
+    Basepoint,
     Name(String),
+    /// A named leaf that also remembers the canonical bytes of its value
+    Value(String, [u8; 32]),
+    /// A named leaf that also keeps the expression that computed it, which is only shown once
+    /// [inlined](Tree::inline_names)
+    Named(String, Box<Tree>),
+    /// The output of a hash function, identified by the type name of the digest and an optional
+    /// domain separator
+    Hash(String, Option<String>),
+    /// A Fiat–Shamir challenge derived from a transcript, as opposed to a prover-side hash
+    Challenge(String),
+    /// A value decoded from its byte encoding
+    FromBytes([u8; 32]),
     Add(Box<Tree>, Box<Tree>),
     Sub(Box<Tree>, Box<Tree>),
     Mul(Box<Tree>, Box<Tree>),
+    /// The multiple of a point by a scalar, with the scalar first
+    ScalarMul(Box<Tree>, Box<Tree>),
+    /// The sum of any number of operands, `0` if there are none
+    AddMany(Vec<Tree>),
+    /// The product of any number of operands, `1` if there are none
+    MulMany(Vec<Tree>),
     Inv(Box<Tree>),
     Neg(Box<Tree>),
+    /// A subexpression raised to a positive power, e.g. by repeated squaring
+    Pow(Box<Tree>, u64),
+    /// A subexpression computed by a variable-time operation
+    Vartime(Box<Tree>),
+    /// A byte of the canonical encoding of a scalar
+    Byte(Box<Tree>, usize),
+    /// An integer clamped for X25519
+    Clamp(Box<Tree>),
+    /// A subexpression with a note attached, which doesn't affect its value
+    Annotated(String, Box<Tree>),
+    /// A point converted to another curve model, identified by the name of the target model
+    Convert(String, Box<Tree>),
 }
 
 impl Debug for Tree {
+    /// Renders the expression in infix notation, or as an indented tree of operations with `{:#?}`
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            return match self {
+                Tree::Add(l, r) => f.debug_tuple("Add").field(l).field(r).finish(),
+                Tree::Sub(l, r) if expand_subtraction() => {
+                    let neg = Tree::Neg(r.clone());
+                    f.debug_tuple("Add").field(l).field(&neg).finish()
+                }
+                Tree::Sub(l, r) => f.debug_tuple("Sub").field(l).field(r).finish(),
+                Tree::Mul(l, r) => f.debug_tuple("Mul").field(l).field(r).finish(),
+                Tree::ScalarMul(l, r) => f.debug_tuple("ScalarMul").field(l).field(r).finish(),
+                Tree::AddMany(xs) => xs
+                    .iter()
+                    .fold(&mut f.debug_tuple("AddMany"), |t, x| t.field(x))
+                    .finish(),
+                Tree::MulMany(xs) => xs
+                    .iter()
+                    .fold(&mut f.debug_tuple("MulMany"), |t, x| t.field(x))
+                    .finish(),
+                Tree::Inv(x) => f.debug_tuple("Inv").field(x).finish(),
+                Tree::Neg(x) => f.debug_tuple("Neg").field(x).finish(),
+                Tree::Pow(x, e) => f.debug_tuple("Pow").field(x).field(e).finish(),
+                Tree::Vartime(x) => f.debug_tuple("Vartime").field(x).finish(),
+                Tree::Annotated(note, x) => {
+                    f.debug_tuple("Annotated").field(note).field(x).finish()
+                }
+                Tree::Byte(x, i) => f.debug_tuple("Byte").field(x).field(i).finish(),
+                Tree::Clamp(x) => f.debug_tuple("Clamp").field(x).finish(),
+                Tree::Convert(model, x) => f.debug_tuple("Convert").field(model).field(x).finish(),
+                leaf => write!(f, "{leaf:?}"),
+            };
+        }
         match self {
             Tree::Zero => f.write_str("0"),
             Tree::One => f.write_str("1"),
             Tree::Unnamed => f.write_str("?"),
-            Tree::Name(s) => f.write_str(s),
-            Tree::Add(l, r) => write!(f, "({l:?} + {r:?})"),
+            Tree::Identity => f.write_str("O"),
+            Tree::Basepoint => f.write_str("B"),
+            Tree::Name(s) | Tree::Value(s, _) | Tree::Named(s, _) => f.write_str(s),
+            Tree::Hash(digest, None) => write!(f, "H<{}>", digest_name(digest)),
+            Tree::Hash(digest, Some(domain)) => {
+                write!(f, "H<{}>({domain:?})", digest_name(digest))
+            }
+            Tree::Challenge(name) => write!(f, "⟨{name}⟩"),
+            Tree::FromBytes(bytes) => write!(f, "bytes({})", elided_hex(bytes)),
+            Tree::Add(l, r) => match subtracted(r) {
+                Some(r) => write!(f, "({l:?} - {r:?})"),
+                None => write!(f, "({l:?} + {r:?})"),
+            },
+            Tree::Sub(l, r) if expand_subtraction() => write!(f, "({l:?} + -{r:?})"),
             Tree::Sub(l, r) => write!(f, "({l:?} - {r:?})"),
             Tree::Mul(l, r) => write!(f, "{l:?} * {r:?}"),
+            Tree::ScalarMul(l, r) => write!(f, "{l:?} · {r:?}"),
+            Tree::AddMany(xs) if xs.is_empty() => f.write_str("0"),
+            Tree::AddMany(xs) => {
+                f.write_str("(")?;
+                fmt_operands(f, xs, " + ")?;
+                f.write_str(")")
+            }
+            Tree::MulMany(xs) if xs.is_empty() => f.write_str("1"),
+            Tree::MulMany(xs) => fmt_operands(f, xs, " * "),
             Tree::Inv(x) => write!(f, "{x:?}⁻¹"),
             Tree::Neg(x) => write!(f, "-{x:?}"),
+            Tree::Pow(x, e) => match **x {
+                Tree::Mul(..) | Tree::MulMany(_) | Tree::Inv(_) | Tree::Neg(_) | Tree::Pow(..) => {
+                    write!(f, "({x:?})^{e}")
+                }
+                _ => write!(f, "{x:?}^{e}"),
+            },
+            Tree::Vartime(x) => write!(f, "⟂({x:?})"),
+            Tree::Annotated(note, x) => write!(f, "/* {note} */ {x:?}"),
+            Tree::Byte(x, i) => match **x {
+                Tree::Mul(..) | Tree::MulMany(_) | Tree::Inv(_) | Tree::Neg(_) | Tree::Pow(..) => {
+                    write!(f, "({x:?})[{i}]")
+                }
+                _ => write!(f, "{x:?}[{i}]"),
+            },
+            Tree::Clamp(x) => write!(f, "clamp({x:?})"),
+            Tree::Convert(model, x) => write!(f, "{model}({x:?})"),
+        }
+    }
+}
+
+fn fmt_operands(f: &mut fmt::Formatter, operands: &[Tree], op: &str) -> fmt::Result {
+    for (i, x) in operands.iter().enumerate() {
+        match subtracted(x) {
+            Some(x) if i > 0 && op == " + " => write!(f, " - {x:?}")?,
+            _ if i > 0 => write!(f, "{op}{x:?}")?,
+            _ => write!(f, "{x:?}")?,
+        }
+    }
+    Ok(())
+}
+
+/// The operand of a negated addend, which is rendered as subtracted instead
+///
+/// Negated addends are kept as they are when subtractions are expanded.
+fn subtracted(addend: &Tree) -> Option<&Tree> {
+    match addend {
+        Tree::Neg(x) if !expand_subtraction() => Some(x),
+        _ => None,
+    }
+}
+
+/// A short name for a digest from its full type name
+///
+/// The wrappers from the `digest` crate and the `typenum` sizes are skipped, as well as the module
+/// paths, so e.g. `Sha512` is named after its `sha2::core_api::Sha512VarCore`.
+fn digest_name(type_name: &str) -> &str {
+    let name = type_name
+        .split(['<', '>', ',', ' '])
+        .find(|path| {
+            !path.is_empty() && !path.starts_with("digest::") && !path.starts_with("typenum::")
+        })
+        .unwrap_or(type_name);
+    let name = name.rsplit("::").next().unwrap_or(name);
+    name.strip_suffix("VarCore")
+        .or_else(|| name.strip_suffix("Core"))
+        .unwrap_or(name)
+}
+
+/// The hexadecimal encoding of the bytes, elided in the middle if set by [`set_hex_elision`]
+pub(crate) fn elided_hex(bytes: &[u8]) -> String {
+    let full = hex(bytes);
+    match hex_elision() {
+        Some(digits) if 2 * digits < full.len() => {
+            format!("{}…{}", &full[..digits], &full[full.len() - digits..])
+        }
+        _ => full,
+    }
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static EXPAND_SUBTRACTION: Cell<bool> = const { Cell::new(false) };
+    static HEX_ELISION: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Makes expressions rendered on the current thread shorten the bytes of their leaves to the
+/// first and last `digits` hexadecimal digits, e.g. `bytes(1a2b…07f0)`, or show them in full
+/// with `None`
+#[cfg(feature = "std")]
+pub fn set_hex_elision(digits: Option<usize>) {
+    HEX_ELISION.with(|e| e.set(digits));
+}
+
+fn hex_elision() -> Option<usize> {
+    #[cfg(feature = "std")]
+    return HEX_ELISION.with(Cell::get);
+    #[cfg(not(feature = "std"))]
+    return None;
+}
+
+/// Makes expressions rendered on the current thread write `a - b` as `a + -b`
+///
+/// Only the rendering changes, the trees still record a subtraction.
+#[cfg(feature = "std")]
+pub fn set_expand_subtraction(expand: bool) {
+    EXPAND_SUBTRACTION.with(|e| e.set(expand));
+}
+
+fn expand_subtraction() -> bool {
+    #[cfg(feature = "std")]
+    return EXPAND_SUBTRACTION.with(Cell::get);
+    #[cfg(not(feature = "std"))]
+    return false;
+}
+
+/// How chains of additions or multiplications are grouped in the trees built by operators
+///
+/// This only affects the shape of the trees, never the computed values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Associativity {
+    /// `a + b + c` is recorded as `(a + b) + c`, the way Rust evaluates it
+    #[default]
+    Left,
+    /// `a + b + c` is recorded as `a + (b + c)`
+    Right,
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static ASSOCIATIVITY: Cell<Associativity> = const { Cell::new(Associativity::Left) };
+}
+
+/// Sets how operators group chained additions and multiplications on the current thread
+#[cfg(feature = "std")]
+pub fn set_associativity(associativity: Associativity) {
+    ASSOCIATIVITY.with(|a| a.set(associativity));
+}
+
+fn associativity() -> Associativity {
+    #[cfg(feature = "std")]
+    return ASSOCIATIVITY.with(Cell::get);
+    #[cfg(not(feature = "std"))]
+    return Associativity::Left;
+}
+
+impl Tree {
+    /// A leaf for the output of the digest `D`
+    #[cfg(feature = "digest")]
+    pub(crate) fn hash<D>(domain: Option<&str>) -> Tree {
+        Tree::Hash(type_name::<D>().into(), domain.map(Into::into))
+    }
+
+    pub(crate) fn sum_of(lhs: Tree, rhs: Tree) -> Tree {
+        match (associativity(), lhs) {
+            (Associativity::Right, Tree::Add(l, r)) => {
+                Tree::Add(l, Box::new(Tree::sum_of(*r, rhs)))
+            }
+            (_, lhs) => Tree::Add(Box::new(lhs), Box::new(rhs)),
+        }
+    }
+
+    pub(crate) fn product_of(lhs: Tree, rhs: Tree) -> Tree {
+        match (associativity(), lhs) {
+            (Associativity::Right, Tree::Mul(l, r)) => {
+                Tree::Mul(l, Box::new(Tree::product_of(*r, rhs)))
+            }
+            (_, lhs) => Tree::Mul(Box::new(lhs), Box::new(rhs)),
+        }
+    }
+
+    pub(crate) fn scalar_mul_of(scalar: Tree, point: Tree) -> Tree {
+        Tree::ScalarMul(Box::new(scalar), Box::new(point))
+    }
+
+    /// The number of nodes on the longest path from the root to a leaf
+    pub fn depth(&self) -> usize {
+        1 + self
+            .children()
+            .into_iter()
+            .map(Tree::depth)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Whether some subexpression was computed by a variable-time operation
+    pub fn contains_vartime(&self) -> bool {
+        matches!(self, Tree::Vartime(_))
+            || self.computed_from().into_iter().any(Tree::contains_vartime)
+    }
+
+    /// The distinct names of the leaves, in order of first appearance
+    pub fn variables(&self) -> Vec<&str> {
+        let mut vars = Vec::new();
+        self.visit_names(&mut |name, _| {
+            if !vars.contains(&name) {
+                vars.push(name)
+            }
+        });
+        vars
+    }
+
+    /// The number of distinct names of the leaves
+    pub fn num_variables(&self) -> usize {
+        let mut names = BTreeSet::new();
+        self.visit_names(&mut |name, _| {
+            names.insert(name);
+        });
+        names.len()
+    }
+
+    /// Checks that every named leaf is one of the `allowed` names, or returns the first one that
+    /// isn't
+    ///
+    /// This can assert that a public value doesn't depend on secrets. Only names are checked:
+    /// unnamed leaves and constants are always allowed.
+    pub fn uses_only(&self, allowed: &[&str]) -> Result<(), String> {
+        match self.variables().into_iter().find(|v| !allowed.contains(v)) {
+            Some(name) => Err(name.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// How many times each named leaf occurs in the expression
+    #[cfg(feature = "std")]
+    pub fn leaf_multiplicities(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        self.visit_names(&mut |name, _| *counts.entry(name.to_owned()).or_default() += 1);
+        counts
+    }
+
+    /// The distinct named leaves with their values, if they were retained
+    pub fn legend(&self) -> Vec<(&str, Option<&[u8; 32]>)> {
+        let mut legend: Vec<(&str, Option<&[u8; 32]>)> = Vec::new();
+        self.visit_names(
+            &mut |name, value| match legend.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, v)) => *v = v.or(value),
+                None => legend.push((name, value)),
+            },
+        );
+        legend
+    }
+
+    fn visit_names<'a>(&'a self, f: &mut impl FnMut(&'a str, Option<&'a [u8; 32]>)) {
+        match self {
+            Tree::Name(s) | Tree::Named(s, _) => f(s, None),
+            Tree::Value(s, bytes) => f(s, Some(bytes)),
+            _ => self.children().into_iter().for_each(|c| c.visit_names(f)),
+        }
+    }
+
+    /// The operands of an addition, a subtraction, a binary multiplication or a scalar
+    /// multiplication
+    pub fn binary_operands(&self) -> Option<(&Tree, &Tree)> {
+        match self {
+            Tree::Add(l, r) | Tree::Sub(l, r) | Tree::Mul(l, r) | Tree::ScalarMul(l, r) => {
+                Some((l, r))
+            }
+            _ => None,
+        }
+    }
+
+    /// The operand of a node with a single subexpression, like a negation or an inversion
+    pub fn unary_operand(&self) -> Option<&Tree> {
+        match self {
+            Tree::Inv(x)
+            | Tree::Neg(x)
+            | Tree::Vartime(x)
+            | Tree::Annotated(_, x)
+            | Tree::Byte(x, _)
+            | Tree::Clamp(x)
+            | Tree::Pow(x, _)
+            | Tree::Convert(_, x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// The direct subexpressions, from left to right
+    pub(crate) fn children(&self) -> Vec<&Tree> {
+        match self {
+            Tree::Zero
+            | Tree::One
+            | Tree::Unnamed
+            | Tree::Identity
+            | Tree::Basepoint
+            | Tree::Name(_)
+            | Tree::Value(..)
+            | Tree::Named(..)
+            | Tree::Hash(..)
+            | Tree::Challenge(_)
+            | Tree::FromBytes(_) => vec![],
+            Tree::Add(l, r) | Tree::Sub(l, r) | Tree::Mul(l, r) | Tree::ScalarMul(l, r) => {
+                vec![l, r]
+            }
+            Tree::AddMany(xs) | Tree::MulMany(xs) => xs.iter().collect(),
+            Tree::Inv(x)
+            | Tree::Neg(x)
+            | Tree::Vartime(x)
+            | Tree::Annotated(_, x)
+            | Tree::Byte(x, _)
+            | Tree::Clamp(x)
+            | Tree::Pow(x, _)
+            | Tree::Convert(_, x) => vec![x],
+        }
+    }
+
+    /// The subexpressions whose operations went into this one: the direct subexpressions, or
+    /// the expression kept by a named leaf
+    ///
+    /// Walkers that measure the work done use this, while those that render or compare the tree
+    /// use [`Tree::children`] and stop at the name.
+    pub(crate) fn computed_from(&self) -> Vec<&Tree> {
+        match self {
+            Tree::Named(_, x) => vec![x],
+            _ => self.children(),
+        }
+    }
+
+    /// Rebuilds this node with each direct subexpression transformed by `f`
+    pub(crate) fn map_children(&self, mut f: impl FnMut(&Tree) -> Tree) -> Tree {
+        let mut f = |x: &Tree| Box::new(f(x));
+        match self {
+            Tree::Zero
+            | Tree::One
+            | Tree::Unnamed
+            | Tree::Identity
+            | Tree::Basepoint
+            | Tree::Name(_)
+            | Tree::Value(..)
+            | Tree::Named(..)
+            | Tree::Hash(..)
+            | Tree::Challenge(_)
+            | Tree::FromBytes(_) => self.clone(),
+            Tree::Add(l, r) => Tree::Add(f(l), f(r)),
+            Tree::Sub(l, r) => Tree::Sub(f(l), f(r)),
+            Tree::Mul(l, r) => Tree::Mul(f(l), f(r)),
+            Tree::ScalarMul(l, r) => Tree::ScalarMul(f(l), f(r)),
+            Tree::AddMany(xs) => Tree::AddMany(xs.iter().map(|x| *f(x)).collect()),
+            Tree::MulMany(xs) => Tree::MulMany(xs.iter().map(|x| *f(x)).collect()),
+            Tree::Inv(x) => Tree::Inv(f(x)),
+            Tree::Neg(x) => Tree::Neg(f(x)),
+            Tree::Vartime(x) => Tree::Vartime(f(x)),
+            Tree::Annotated(note, x) => Tree::Annotated(note.clone(), f(x)),
+            Tree::Byte(x, i) => Tree::Byte(f(x), *i),
+            Tree::Clamp(x) => Tree::Clamp(f(x)),
+            Tree::Pow(x, e) => Tree::Pow(f(x), *e),
+            Tree::Convert(model, x) => Tree::Convert(model.clone(), f(x)),
         }
     }
 }
+
+#[test]
+fn test_num_variables() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named_with_value("b");
+
+    assert_eq!((&a * &b + &a).tree.num_variables(), 2);
+    assert_eq!(Tree::One.num_variables(), 0);
+}
+
+#[test]
+fn test_uses_only() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named_with_value("b");
+    let sum = (&a + &b).tree;
+
+    assert_eq!(sum.uses_only(&["a", "b"]), Ok(()));
+    assert_eq!(sum.uses_only(&["a"]), Err("b".into()));
+    assert_eq!(
+        Tree::product_of(Tree::One, sum).uses_only(&[]),
+        Err("a".into())
+    );
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_leaf_multiplicities() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let x = &a * &a + &b;
+
+    assert_eq!(x.tree.variables(), ["a", "b"]);
+    assert_eq!(
+        x.tree.leaf_multiplicities(),
+        HashMap::from([("a".to_owned(), 2), ("b".to_owned(), 1)])
+    );
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_associativity() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let c = TestScalar::from(5u8).named("c");
+
+    let left = &a + &b + &c;
+    set_associativity(Associativity::Right);
+    let right = &a + &b + &c;
+    set_associativity(Associativity::Left);
+
+    assert_eq!(left, right);
+    assert_eq!(format!("{:?}", left.tree), "((a + b) + c)");
+    assert_eq!(format!("{:?}", right.tree), "(a + (b + c))");
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_expand_subtraction() {
+    use crate::{
+        ristretto::{RistrettoPoint, TestRistrettoPoint},
+        scalar::TestScalar,
+        Named,
+    };
+
+    let c = TestScalar::from(2u8).named("c");
+    let x = TestScalar::from(3u8).named("x");
+    let r = TestRistrettoPoint::mul_base(&c).named("R");
+    let big_x = TestRistrettoPoint::mul_base(&x).named("X");
+    let s = &r - &c * &big_x;
+
+    assert_eq!(format!("{:?}", s.tree), "(R - c · X)");
+    set_expand_subtraction(true);
+    let expanded = (format!("{:?}", s.tree), format!("{:#?}", s.tree));
+    set_expand_subtraction(false);
+
+    assert_eq!(expanded.0, "(R + -c · X)");
+    assert!(expanded.1.starts_with("Add(\n    R,\n    Neg(\n"));
+}
+
+#[test]
+fn test_nary_rendering() {
+    let [a, b, c] = ["a", "b", "c"].map(|n| Tree::Name(n.into()));
+    let sum = Tree::AddMany(vec![a.clone(), b.clone(), c.clone()]);
+    let product = Tree::MulMany(vec![a, sum.clone(), b]);
+
+    assert_eq!(format!("{sum:?}"), "(a + b + c)");
+    assert_eq!(format!("{product:?}"), "a * (a + b + c) * b");
+    assert_eq!(format!("{:?}", Tree::AddMany(vec![])), "0");
+    assert_eq!(format!("{:?}", Tree::MulMany(vec![])), "1");
+    assert_eq!(format!("{sum:#?}"), "AddMany(\n    a,\n    b,\n    c,\n)");
+    assert!(product.to_sympy().ends_with("expr = a*(a + b + c)*b\n"));
+    assert!(product.to_smtlib().contains("(mod (* a (+ a b c) b) l)"));
+}
+
+#[test]
+fn test_named_keeping_work() {
+    use crate::{
+        ristretto::{RistrettoPoint, TestRistrettoPoint},
+        scalar::TestScalar,
+        Named,
+    };
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&a).named("P");
+    let r = TestRistrettoPoint::vartime_double_scalar_mul_basepoint(&a, &p, &b);
+    let model = CostModel::default();
+
+    let kept = r.clone().named_keeping("R");
+    assert!(kept.tree.contains_vartime());
+    assert_eq!(kept.tree.scalar_mul_count(), 2);
+    assert_eq!(
+        kept.tree.estimated_cost(&model),
+        r.tree.estimated_cost(&model)
+    );
+
+    let named = r.named("R");
+    assert!(!named.tree.contains_vartime());
+    assert_eq!(named.tree.scalar_mul_count(), 0);
+    assert_eq!(named.tree.estimated_cost(&model), 0);
+}
+
+#[test]
+fn test_operand_accessors() {
+    let [a, b] = ["a", "b"].map(|n| Tree::Name(n.into()));
+    let sum = Tree::Add(Box::new(a.clone()), Box::new(b.clone()));
+    let neg = Tree::Neg(Box::new(sum.clone()));
+
+    assert_eq!(sum.binary_operands(), Some((&a, &b)));
+    assert_eq!(sum.unary_operand(), None);
+    assert_eq!(neg.unary_operand(), Some(&sum));
+    assert_eq!(a.binary_operands(), None);
+    assert_eq!(a.unary_operand(), None);
+}
+
+#[test]
+fn test_negated_addends() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let c = TestScalar::from(5u8).named("c");
+
+    let x = &a + -(&b * &c);
+    assert_eq!(format!("{:?}", x.tree), "(a - b * c)");
+    assert_eq!(format!("{:?}", x.tree.flatten()), "(a - b * c)");
+    assert_eq!(format!("{:?}", (-&a + &b).tree), "(-a + b)");
+    #[cfg(feature = "std")]
+    {
+        set_expand_subtraction(true);
+        let expanded = format!("{:?}", x.tree);
+        set_expand_subtraction(false);
+        assert_eq!(expanded, "(a + -b * c)");
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_hex_elision() {
+    let mut bytes = [0xa5; 32];
+    bytes[..2].copy_from_slice(&[0x1a, 0x2b]);
+    bytes[30..].copy_from_slice(&[0x07, 0xf0]);
+    let leaf = Tree::Clamp(Box::new(Tree::FromBytes(bytes)));
+
+    let full = format!("{leaf:?}");
+    set_hex_elision(Some(4));
+    let elided = format!("{leaf:?}");
+    set_hex_elision(Some(32));
+    let long = format!("{leaf:?}");
+    set_hex_elision(None);
+
+    assert_eq!(full, format!("clamp(bytes(1a2b{}07f0))", "a5".repeat(28)));
+    assert_eq!(elided, "clamp(bytes(1a2b…07f0))");
+    assert_eq!(long, full);
+}
@@ -0,0 +1,72 @@
+//! Rendering of expression trees as indented ASCII art, like the output of `tree`
+
+use alloc::{format, string::String};
+
+use super::Tree;
+
+impl Tree {
+    /// Renders the expression with one node per line, the operands of each operation below it
+    ///
+    /// ```text
+    /// *
+    /// ├── +
+    /// │   ├── a
+    /// │   └── b
+    /// └── c
+    /// ```
+    pub fn to_art(&self) -> String {
+        let mut out = String::new();
+        draw(self, "", "", &mut out);
+        out
+    }
+}
+
+/// Appends the lines of `tree`, the first one after `first` and the others after `rest`
+fn draw(tree: &Tree, first: &str, rest: &str, out: &mut String) {
+    out.push_str(&format!("{first}{}\n", label(tree)));
+    let children = tree.children();
+    for (i, child) in children.iter().enumerate() {
+        if i + 1 < children.len() {
+            draw(child, &format!("{rest}├── "), &format!("{rest}│   "), out);
+        } else {
+            draw(child, &format!("{rest}└── "), &format!("{rest}    "), out);
+        }
+    }
+}
+
+fn label(tree: &Tree) -> String {
+    match tree {
+        Tree::Add(..) | Tree::AddMany(_) if !tree.children().is_empty() => "+".into(),
+        Tree::Sub(..) | Tree::Neg(_) => "-".into(),
+        Tree::Mul(..) | Tree::MulMany(_) if !tree.children().is_empty() => "*".into(),
+        Tree::ScalarMul(..) => "·".into(),
+        Tree::Inv(_) => "⁻¹".into(),
+        Tree::Pow(_, e) => format!("^{e}"),
+        Tree::Vartime(_) => "⟂".into(),
+        Tree::Byte(_, i) => format!("[{i}]"),
+        Tree::Clamp(_) => "clamp".into(),
+        Tree::Convert(model, _) => model.clone(),
+        Tree::Annotated(note, _) => format!("/* {note} */"),
+        leaf => format!("{leaf:?}"),
+    }
+}
+
+#[test]
+fn test_to_art() {
+    use crate::{
+        scalar::{Scalar, TestScalar},
+        Named,
+    };
+
+    let [a, b, c] = ["a", "b", "c"].map(|n| TestScalar::from(2u8).named(n));
+
+    assert_eq!(
+        (&(&a + &b) * &c).tree.to_art(),
+        "*\n├── +\n│   ├── a\n│   └── b\n└── c\n"
+    );
+    assert_eq!(
+        (-&(&a * &b).invert()).tree.to_art(),
+        "-\n└── ⁻¹\n    └── *\n        ├── a\n        └── b\n"
+    );
+    assert_eq!(Tree::AddMany(vec![]).to_art(), "0\n");
+}
@@ -0,0 +1,81 @@
+//! Constructors for building trees directly, without computing any values
+//!
+//! These are shorthands for the variants of [`Tree`], e.g. `add(name("a"), name("b"))` for
+//! `a + b`, meant for testing the renderings and analyses of trees.
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+use super::Tree;
+
+/// A named leaf
+pub fn name<S>(name: S) -> Tree
+where
+    String: From<S>,
+{
+    Tree::Name(name.into())
+}
+
+/// The sum `l + r`
+pub fn add(l: Tree, r: Tree) -> Tree {
+    Tree::Add(Box::new(l), Box::new(r))
+}
+
+/// The difference `l - r`
+pub fn sub(l: Tree, r: Tree) -> Tree {
+    Tree::Sub(Box::new(l), Box::new(r))
+}
+
+/// The product `l * r` of scalars
+pub fn mul(l: Tree, r: Tree) -> Tree {
+    Tree::Mul(Box::new(l), Box::new(r))
+}
+
+/// The multiple of the point `p` by the scalar `s`
+pub fn scalar_mul(s: Tree, p: Tree) -> Tree {
+    Tree::ScalarMul(Box::new(s), Box::new(p))
+}
+
+/// The negation `-x`
+pub fn neg(x: Tree) -> Tree {
+    Tree::Neg(Box::new(x))
+}
+
+/// The inverse `x⁻¹`
+pub fn inv(x: Tree) -> Tree {
+    Tree::Inv(Box::new(x))
+}
+
+/// The n-ary sum of the operands
+pub fn sum(operands: impl IntoIterator<Item = Tree>) -> Tree {
+    Tree::AddMany(operands.into_iter().collect::<Vec<_>>())
+}
+
+/// The n-ary product of the operands
+pub fn product(operands: impl IntoIterator<Item = Tree>) -> Tree {
+    Tree::MulMany(operands.into_iter().collect::<Vec<_>>())
+}
+
+#[test]
+fn test_build() {
+    let [a, b, c] = ["a", "b", "c"].map(name);
+
+    assert_eq!(format!("{:?}", add(a.clone(), b.clone())), "(a + b)");
+    assert_eq!(format!("{:?}", sub(a.clone(), neg(b.clone()))), "(a - -b)");
+    assert_eq!(
+        format!("{:?}", mul(inv(a.clone()), add(b.clone(), c.clone()))),
+        "a⁻¹ * (b + c)"
+    );
+    assert_eq!(
+        format!(
+            "{:?}",
+            scalar_mul(product([a.clone(), b.clone()]), name("P"))
+        ),
+        "a * b · P"
+    );
+    assert_eq!(
+        format!("{:?}", sum([a.clone(), neg(b.clone()), c.clone()])),
+        "(a - b + c)"
+    );
+    assert_eq!(sub(a.clone(), sub(b, c)).render_minimal(), "a - (b - c)");
+    assert_eq!(format!("{:?}", sum([])), "0");
+}
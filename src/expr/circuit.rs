@@ -0,0 +1,179 @@
+//! Translation of expression trees into arithmetic circuits
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use super::Tree;
+
+/// The index of the gate whose output a wire carries
+pub type Wire = usize;
+
+/// A gate of an arithmetic circuit, whose output is the wire of the same index
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Gate {
+    /// An input of the circuit, given by a leaf or by a subexpression that isn't arithmetic
+    Input(Tree),
+    Add(Wire, Wire),
+    Sub(Wire, Wire),
+    Mul(Wire, Wire),
+    Neg(Wire),
+    Inv(Wire),
+}
+
+impl Tree {
+    /// Flattens the expression into a circuit in static single assignment form
+    ///
+    /// Every gate only uses the wires of the gates before it, and the output of the whole
+    /// expression is the last one. Structurally equal subexpressions share their wires, except for
+    /// unnamed, hashed, challenge and decoded leaves, which may stand for different values even
+    /// when they look the same: each of them is an input of its own, and nothing computed from
+    /// them is shared. Sums and products of many operands become chains of binary gates, scalar
+    /// multiplications of points become multiplication gates, powers become chains of squarings
+    /// and multiplications, and variable-time markers and annotations are looked through.
+    /// Any other node, like a hash or a byte, is an input.
+    pub fn to_gates(&self) -> Vec<Gate> {
+        let mut circuit = Circuit::default();
+        circuit.wire(self);
+        circuit.gates
+    }
+}
+
+#[derive(Default)]
+struct Circuit<'a> {
+    gates: Vec<Gate>,
+    wires: BTreeMap<&'a Tree, Wire>,
+    /// Whether the subexpression being wired depends on a leaf that can't be shared
+    opaque: bool,
+}
+
+impl<'a> Circuit<'a> {
+    /// The wire carrying the value of `tree`, adding the gates that compute it if needed
+    fn wire(&mut self, tree: &'a Tree) -> Wire {
+        if let Tree::Unnamed | Tree::Hash(..) | Tree::Challenge(_) | Tree::FromBytes(_) = tree {
+            self.opaque = true;
+            return self.push(Gate::Input(tree.clone()));
+        }
+        if let Some(&w) = self.wires.get(tree) {
+            return w;
+        }
+        let outer = core::mem::take(&mut self.opaque);
+        let w = match tree {
+            Tree::Add(l, r) => self.binary(l, r, Gate::Add),
+            Tree::Sub(l, r) => self.binary(l, r, Gate::Sub),
+            Tree::Mul(l, r) | Tree::ScalarMul(l, r) => self.binary(l, r, Gate::Mul),
+            Tree::AddMany(xs) if !xs.is_empty() => self.chain(xs, Gate::Add),
+            Tree::MulMany(xs) if !xs.is_empty() => self.chain(xs, Gate::Mul),
+            Tree::AddMany(_) => self.push(Gate::Input(Tree::Zero)),
+            Tree::MulMany(_) => self.push(Gate::Input(Tree::One)),
+            Tree::Neg(x) => {
+                let x = self.wire(x);
+                self.push(Gate::Neg(x))
+            }
+            Tree::Inv(x) => {
+                let x = self.wire(x);
+                self.push(Gate::Inv(x))
+            }
+            Tree::Pow(x, e) if *e > 0 => {
+                let x = self.wire(x);
+                self.pow(x, *e)
+            }
+            Tree::Pow(..) => self.push(Gate::Input(Tree::One)),
+            Tree::Vartime(x) | Tree::Annotated(_, x) => self.wire(x),
+            _ => self.push(Gate::Input(tree.clone())),
+        };
+        if !self.opaque {
+            self.wires.insert(tree, w);
+        }
+        self.opaque |= outer;
+        w
+    }
+
+    fn push(&mut self, gate: Gate) -> Wire {
+        self.gates.push(gate);
+        self.gates.len() - 1
+    }
+
+    fn binary(&mut self, l: &'a Tree, r: &'a Tree, gate: fn(Wire, Wire) -> Gate) -> Wire {
+        let (l, r) = (self.wire(l), self.wire(r));
+        self.push(gate(l, r))
+    }
+
+    /// Raises `base` to the positive power `e` by square-and-multiply
+    fn pow(&mut self, mut base: Wire, mut e: u64) -> Wire {
+        let mut acc = None;
+        loop {
+            if e & 1 == 1 {
+                acc = Some(match acc {
+                    Some(acc) => self.push(Gate::Mul(acc, base)),
+                    None => base,
+                });
+            }
+            e >>= 1;
+            if e == 0 {
+                return acc.unwrap();
+            }
+            base = self.push(Gate::Mul(base, base));
+        }
+    }
+
+    /// Combines the operands from left to right with binary gates
+    fn chain(&mut self, operands: &'a [Tree], gate: fn(Wire, Wire) -> Gate) -> Wire {
+        let first = self.wire(&operands[0]);
+        operands[1..].iter().fold(first, |acc, x| {
+            let w = self.wire(x);
+            self.push(gate(acc, w))
+        })
+    }
+}
+
+#[test]
+fn test_to_gates() {
+    use crate::{scalar::TestScalar, Named};
+
+    let [a, b, c] = ["a", "b", "c"].map(|n| TestScalar::from(2u8).named(n));
+
+    assert_eq!(
+        (&(&a + &b) * &c).tree.to_gates(),
+        [
+            Gate::Input(a.tree.clone()),
+            Gate::Input(b.tree.clone()),
+            Gate::Add(0, 1),
+            Gate::Input(c.tree.clone()),
+            Gate::Mul(2, 3),
+        ]
+    );
+
+    let s = &a + &b;
+    assert_eq!(
+        (&s * &s).tree.to_gates(),
+        [
+            Gate::Input(a.tree),
+            Gate::Input(b.tree),
+            Gate::Add(0, 1),
+            Gate::Mul(2, 2),
+        ]
+    );
+}
+
+#[test]
+fn test_to_gates_opaque_leaves() {
+    use alloc::boxed::Box;
+
+    let u = Box::new(Tree::Unnamed);
+    let a = Box::new(Tree::Name("a".into()));
+    let tree = Tree::Add(
+        Box::new(Tree::Mul(u.clone(), a.clone())),
+        Box::new(Tree::Mul(u, a)),
+    );
+
+    assert_eq!(
+        tree.to_gates(),
+        [
+            Gate::Input(Tree::Unnamed),
+            Gate::Input(Tree::Name("a".into())),
+            Gate::Mul(0, 1),
+            Gate::Input(Tree::Unnamed),
+            Gate::Mul(3, 1),
+            Gate::Add(2, 4),
+        ]
+    );
+}
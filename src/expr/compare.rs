@@ -0,0 +1,244 @@
+//! Structural comparison of expression trees
+
+use alloc::{boxed::Box, collections::BTreeMap, vec, vec::Vec};
+
+use super::Tree;
+
+pub(super) type Split = fn(&Tree) -> Option<Vec<&Tree>>;
+type Node = fn(Box<Tree>, Box<Tree>) -> Tree;
+
+impl Tree {
+    /// Rewrites the expression into a canonical form where the operands of chains of additions
+    /// and multiplications are flattened and sorted
+    ///
+    /// Two expressions that only differ by the order or the grouping of their sums and products
+    /// have the same canonical form.
+    pub fn canonicalize(&self) -> Tree {
+        match self {
+            Tree::Add(..) | Tree::AddMany(_) => {
+                canonical_chain(self, split_add, Tree::Add, Tree::Zero)
+            }
+            Tree::Mul(..) | Tree::MulMany(_) => {
+                canonical_chain(self, split_mul, Tree::Mul, Tree::One)
+            }
+            _ => self.map_children(Tree::canonicalize),
+        }
+    }
+
+    /// The number of addends of a sum, or of factors of a product, once nested chains are
+    /// flattened
+    ///
+    /// Variable-time markers are looked through; any other expression counts as a single term.
+    pub fn term_count(&self) -> usize {
+        match self {
+            Tree::Add(..) | Tree::AddMany(_) => chain_len(self, split_add),
+            Tree::Mul(..) | Tree::MulMany(_) => chain_len(self, split_mul),
+            Tree::Vartime(x) | Tree::Annotated(_, x) => x.term_count(),
+            _ => 1,
+        }
+    }
+
+    /// The first pair of subtrees, in depth-first order, where the two expressions differ
+    pub fn first_difference<'a>(&'a self, other: &'a Tree) -> Option<(&'a Tree, &'a Tree)> {
+        if self == other {
+            return None;
+        }
+        // compare the nodes themselves, ignoring their children
+        let hollow = |t: &Tree| t.map_children(|_| Tree::Unnamed);
+        if hollow(self) != hollow(other) {
+            return Some((self, other));
+        }
+        Iterator::zip(self.children().into_iter(), other.children())
+            .find_map(|(l, r)| l.first_difference(r))
+    }
+
+    /// Whether the expressions have the same structure up to a consistent renaming of their
+    /// named leaves
+    ///
+    /// Each name must always correspond to the same name in the other expression, and distinct
+    /// names to distinct names: `a + b` matches `x + y` but not `x + x`. The values kept by
+    /// leaves are ignored.
+    pub fn eq_modulo_renaming(&self, other: &Tree) -> bool {
+        renaming(self, other, &mut BTreeMap::new(), &mut BTreeMap::new())
+    }
+
+    /// Removes the labels of the expression, keeping only the shape of the computation
+    ///
+    /// Notes are dropped along with their [`Tree::Annotated`] wrapper, named leaves that kept
+    /// their expression are replaced by it, and the other named leaves, with or without a value,
+    /// become [`Tree::Unnamed`]. Everything that says how a value was obtained rather than what it
+    /// is called is kept: constants, hashes, challenges, decoded bytes and variable-time markers.
+    pub fn strip_metadata(&self) -> Tree {
+        match self {
+            Tree::Annotated(_, x) | Tree::Named(_, x) => x.strip_metadata(),
+            Tree::Name(_) | Tree::Value(..) => Tree::Unnamed,
+            _ => self.map_children(Tree::strip_metadata),
+        }
+    }
+
+    /// Replaces the named leaves that kept their expression, as recorded by
+    /// [`Tracked::named_keeping`](crate::tracked::Tracked::named_keeping), with that expression
+    ///
+    /// This shows the whole history of a value at the cost of a longer rendering.
+    pub fn inline_names(&self) -> Tree {
+        match self {
+            Tree::Named(_, x) => x.inline_names(),
+            _ => self.map_children(Tree::inline_names),
+        }
+    }
+}
+
+/// Matches the trees, extending the correspondence between their names in both directions
+fn renaming<'a>(
+    l: &'a Tree,
+    r: &'a Tree,
+    forward: &mut BTreeMap<&'a str, &'a str>,
+    backward: &mut BTreeMap<&'a str, &'a str>,
+) -> bool {
+    match (l, r) {
+        (
+            Tree::Name(x) | Tree::Value(x, _) | Tree::Named(x, _),
+            Tree::Name(y) | Tree::Value(y, _) | Tree::Named(y, _),
+        ) => *forward.entry(x).or_insert(y) == y && *backward.entry(y).or_insert(x) == x,
+        _ => {
+            let hollow = |t: &Tree| t.map_children(|_| Tree::Unnamed);
+            hollow(l) == hollow(r)
+                && Iterator::zip(l.children().into_iter(), r.children())
+                    .all(|(l, r)| renaming(l, r, forward, backward))
+        }
+    }
+}
+
+pub(super) fn split_add(tree: &Tree) -> Option<Vec<&Tree>> {
+    match tree {
+        Tree::Add(l, r) => Some(vec![l, r]),
+        Tree::AddMany(xs) => Some(xs.iter().collect()),
+        _ => None,
+    }
+}
+
+pub(super) fn split_mul(tree: &Tree) -> Option<Vec<&Tree>> {
+    match tree {
+        Tree::Mul(l, r) => Some(vec![l, r]),
+        Tree::MulMany(xs) => Some(xs.iter().collect()),
+        _ => None,
+    }
+}
+
+/// Rebuilds a chain with its operands sorted, as a left-associated chain of binary nodes
+///
+/// Binary and n-ary chains have the same canonical form.
+fn canonical_chain(tree: &Tree, split: Split, node: Node, empty: Tree) -> Tree {
+    let mut operands = Vec::new();
+    collect_operands(tree, split, &mut operands);
+    operands.sort();
+    operands
+        .into_iter()
+        .reduce(|l, r| node(Box::new(l), Box::new(r)))
+        .unwrap_or(empty)
+}
+
+fn chain_len(tree: &Tree, split: Split) -> usize {
+    match split(tree) {
+        Some(operands) => operands.into_iter().map(|x| chain_len(x, split)).sum(),
+        None => 1,
+    }
+}
+
+fn collect_operands(tree: &Tree, split: Split, out: &mut Vec<Tree>) {
+    match split(tree) {
+        Some(operands) => operands
+            .into_iter()
+            .for_each(|x| collect_operands(x, split, out)),
+        None => out.push(tree.canonicalize()),
+    }
+}
+
+#[test]
+fn test_canonicalize() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let c = TestScalar::from(5u8).named("c");
+
+    let x = (&a + &b) + &c * &a;
+    let y = &a * &c + (&b + &a);
+    assert_ne!(x.tree, y.tree);
+    assert_eq!(x.tree.canonicalize(), y.tree.canonicalize());
+
+    let z = &a * &b + (&b + &a);
+    let (x, z) = (x.tree.canonicalize(), z.tree.canonicalize());
+    let (l, r) = x.first_difference(&z).unwrap();
+    assert_eq!(format!("{l:?}"), "c");
+    assert_eq!(format!("{r:?}"), "b");
+}
+
+#[test]
+fn test_term_count() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let c = TestScalar::from(5u8).named("c");
+
+    assert_eq!((&a + &b + &c).tree.term_count(), 3);
+    assert_eq!((&a + (&b + &c)).tree.term_count(), 3);
+    assert_eq!((&a * &b).tree.term_count(), 2);
+    assert_eq!((&a * &b + &c).tree.term_count(), 2);
+    assert_eq!(a.tree.term_count(), 1);
+}
+
+#[test]
+fn test_eq_modulo_renaming() {
+    use crate::{scalar::TestScalar, Named};
+
+    let [a, b, x, y] = ["a", "b", "x", "y"].map(|n| TestScalar::from(2u8).named(n));
+
+    assert!((&a + &b).tree.eq_modulo_renaming(&(&x + &y).tree));
+    assert!((&a * &b - &a).tree.eq_modulo_renaming(&(&b * &a - &b).tree));
+    assert!(!(&a + &b).tree.eq_modulo_renaming(&(&a + &a).tree));
+    assert!(!(&a + &a).tree.eq_modulo_renaming(&(&a + &b).tree));
+    assert!(!(&a + &b).tree.eq_modulo_renaming(&(&a * &b).tree));
+}
+
+#[test]
+fn test_strip_metadata() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named_with_value("b");
+    let c = TestScalar::from(5u8);
+
+    let annotated = &(&a * &b).annotate("cross term") + &a;
+    let plain = &(&c * &c) + &TestScalar::from(7u8).named("x");
+    assert_ne!(annotated.tree, plain.tree);
+    assert_eq!(annotated.tree.strip_metadata(), plain.tree.strip_metadata());
+    assert_eq!(
+        format!("{:?}", annotated.tree.strip_metadata()),
+        "(? * ? + ?)"
+    );
+    assert_ne!(
+        annotated.tree.strip_metadata(),
+        (&a + &b).tree.strip_metadata()
+    );
+}
+
+#[test]
+fn test_inline_names() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let s = (&a + &b).named_keeping("s");
+    let x = &s * &s.clone().named("t");
+
+    assert_eq!(format!("{:?}", x.tree), "s * t");
+    assert_eq!(x.tree.variables(), ["s", "t"]);
+    assert_eq!(format!("{:?}", x.tree.inline_names()), "(a + b) * t");
+    assert_eq!((&s * &a).tree.inline_names(), (&(&a + &b) * &a).tree);
+    assert_eq!(
+        (&s * &a).tree.strip_metadata(),
+        (&(&a + &b) * &a).tree.strip_metadata()
+    );
+}
@@ -0,0 +1,215 @@
+//! Estimation of the cost of evaluating expression trees
+
+use alloc::{vec, vec::Vec};
+
+use super::{compare::split_add, Tree};
+
+/// The relative cost of each kind of operation
+///
+/// The trees don't record the types of the operands, so additions cost the same whether they
+/// add scalars or points. Scalar multiplications of points are told apart from products of
+/// scalars, and a sum of several of them is taken to be computed as a single multiscalar
+/// multiplication, costing `multiscalar_term` per term instead of `scalar_mul`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CostModel {
+    pub add: u64,
+    pub sub: u64,
+    pub mul: u64,
+    pub scalar_mul: u64,
+    pub multiscalar_term: u64,
+    pub neg: u64,
+    pub invert: u64,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            add: 1,
+            sub: 1,
+            mul: 10,
+            scalar_mul: 10,
+            multiscalar_term: 6,
+            neg: 1,
+            invert: 100,
+        }
+    }
+}
+
+impl Tree {
+    /// The total cost of the operations in the expression, including those kept under a name by
+    /// [`Tracked::named_keeping`](crate::tracked::Tracked::named_keeping); leaves are free
+    pub fn estimated_cost(&self, model: &CostModel) -> u64 {
+        if let Some(cost) = self.multiscalar_cost(model) {
+            return cost;
+        }
+        let own = match self {
+            Tree::Add(..) => model.add,
+            Tree::Sub(..) => model.sub,
+            Tree::Mul(..) => model.mul,
+            Tree::ScalarMul(..) => model.scalar_mul,
+            Tree::AddMany(xs) => model.add * xs.len().saturating_sub(1) as u64,
+            Tree::MulMany(xs) => model.mul * xs.len().saturating_sub(1) as u64,
+            Tree::Neg(_) => model.neg,
+            Tree::Inv(_) => model.invert,
+            // the squarings and the multiplications of square-and-multiply
+            Tree::Pow(_, e) if *e > 0 => {
+                model.mul * u64::from(63 - e.leading_zeros() + e.count_ones() - 1)
+            }
+            _ => 0,
+        };
+        own + self
+            .computed_from()
+            .into_iter()
+            .map(|c| c.estimated_cost(model))
+            .sum::<u64>()
+    }
+
+    /// The cost of a sum of at least two scalar multiplications, batched together
+    fn multiscalar_cost(&self, model: &CostModel) -> Option<u64> {
+        split_add(self)?;
+        let mut terms = vec![];
+        addends(self, &mut terms);
+        if terms.len() < 2 || !terms.iter().all(|t| matches!(t, Tree::ScalarMul(..))) {
+            return None;
+        }
+        let n = terms.len() as u64;
+        let operands: u64 = terms
+            .iter()
+            .flat_map(|t| t.children())
+            .map(|c| c.estimated_cost(model))
+            .sum();
+        Some(n * model.multiscalar_term + (n - 1) * model.add + operands)
+    }
+}
+
+/// The addends of a chain of sums
+fn addends<'a>(tree: &'a Tree, out: &mut Vec<&'a Tree>) {
+    match split_add(tree) {
+        Some(operands) => operands.into_iter().for_each(|x| addends(x, out)),
+        None => out.push(tree),
+    }
+}
+
+impl Tree {
+    /// The number of scalar multiplications of points in the expression
+    ///
+    /// Besides the [`Tree::ScalarMul`] nodes recorded by the point operators, plain products
+    /// count if their last operand looks like a point, i.e. it is the basepoint, the identity, a
+    /// leaf whose name starts with an uppercase letter, or built from such points by sums,
+    /// negations and products. This heuristic only matters for trees built by other means.
+    pub fn scalar_mul_count(&self) -> usize {
+        let own = match self {
+            Tree::ScalarMul(..) => 1,
+            Tree::Mul(_, x) if x.is_point_like() => 1,
+            Tree::MulMany(xs) if xs.last().is_some_and(Tree::is_point_like) => 1,
+            _ => 0,
+        };
+        own + self
+            .computed_from()
+            .into_iter()
+            .map(Tree::scalar_mul_count)
+            .sum::<usize>()
+    }
+
+    fn is_point_like(&self) -> bool {
+        match self {
+            Tree::Basepoint | Tree::Identity | Tree::ScalarMul(..) | Tree::Convert(..) => true,
+            Tree::Name(s) | Tree::Value(s, _) | Tree::Named(s, _) => {
+                s.starts_with(char::is_uppercase)
+            }
+            Tree::Add(l, r) | Tree::Sub(l, r) => l.is_point_like() || r.is_point_like(),
+            Tree::AddMany(xs) => xs.iter().any(Tree::is_point_like),
+            Tree::Mul(_, x) | Tree::Neg(x) | Tree::Vartime(x) | Tree::Annotated(_, x) => {
+                x.is_point_like()
+            }
+            Tree::MulMany(xs) => xs.last().is_some_and(Tree::is_point_like),
+            _ => false,
+        }
+    }
+}
+
+/// The expression with the lower [estimated cost](Tree::estimated_cost), or `a` if they cost the
+/// same
+pub fn cheaper_of<'a>(a: &'a Tree, b: &'a Tree, model: &CostModel) -> &'a Tree {
+    if b.estimated_cost(model) < a.estimated_cost(model) {
+        b
+    } else {
+        a
+    }
+}
+
+#[test]
+fn test_cheaper_of() {
+    use crate::{
+        ristretto::{RistrettoPoint, TestRistrettoPoint},
+        scalar::TestScalar,
+        Named,
+    };
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&a).named("P");
+
+    let separate = &a * &p + &b * &p;
+    let combined = TestRistrettoPoint::multiscalar_mul([&a + &b], [&p]);
+    let model = CostModel::default();
+
+    assert_eq!(separate, combined);
+    assert_eq!(separate.tree.estimated_cost(&model), 13);
+    assert_eq!(combined.tree.estimated_cost(&model), 11);
+    assert_eq!(
+        cheaper_of(&separate.tree, &combined.tree, &model),
+        &combined.tree
+    );
+    assert_eq!(
+        cheaper_of(&combined.tree, &separate.tree, &model),
+        &combined.tree
+    );
+}
+
+#[test]
+fn test_scalar_mul_count() {
+    use crate::{
+        ristretto::{RistrettoPoint, TestRistrettoPoint},
+        scalar::TestScalar,
+        Named,
+    };
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&a).named("P");
+    let q = TestRistrettoPoint::mul_base(&b).named("Q");
+
+    assert_eq!((&a * &p + &b * &q).tree.scalar_mul_count(), 2);
+    assert_eq!((&(&a * &b) * &(&p + &q)).tree.scalar_mul_count(), 1);
+    assert_eq!(
+        TestRistrettoPoint::mul_base_clamped([1; 32])
+            .tree
+            .scalar_mul_count(),
+        1
+    );
+    assert_eq!((&a * &b).tree.scalar_mul_count(), 0);
+}
+
+#[test]
+fn test_multiscalar_cost() {
+    use crate::{
+        ristretto::{RistrettoPoint, TestRistrettoPoint},
+        scalar::TestScalar,
+        Named,
+    };
+
+    let [a, b, c] = ["a", "b", "c"].map(|n| TestScalar::from(2u8).named(n));
+    let [p, q, r] = ["P", "Q", "R"].map(|n| TestRistrettoPoint::basepoint().named(n));
+    let model = CostModel::default();
+
+    let batched = TestRistrettoPoint::multiscalar_mul([&a, &b, &c], [&p, &q, &r]).tree;
+    let unbatched = 3 * model.scalar_mul + 2 * model.add;
+    assert_eq!(batched.estimated_cost(&model), 20);
+    assert!(batched.estimated_cost(&model) < unbatched);
+
+    let single = (&a * &p).tree;
+    assert_eq!(single.estimated_cost(&model), model.scalar_mul);
+    let mixed = (&a * &p + &q).tree;
+    assert_eq!(mixed.estimated_cost(&model), model.scalar_mul + model.add);
+}
@@ -0,0 +1,109 @@
+//! Evaluation of expression trees from the values kept in their leaves
+
+use curve25519_dalek::scalar::{clamp_integer, Scalar as DalekScalar};
+
+use super::Tree;
+
+impl Tree {
+    /// Recomputes the value of a scalar expression from the values kept in its leaves
+    ///
+    /// Leaves keep their value when named with
+    /// [`TestScalar::named_with_value`](crate::scalar::TestScalar::named_with_value).
+    ///
+    /// # Panics
+    ///
+    /// If some leaf has no known value; see [`Tree::try_evaluate`].
+    pub fn evaluate(&self) -> DalekScalar {
+        self.try_evaluate()
+            .expect("the expression has leaves without a known value")
+    }
+
+    /// Recomputes the value of a scalar expression, if all its leaves have a known value
+    pub fn try_evaluate(&self) -> Option<DalekScalar> {
+        let binary = |l: &Tree, r: &Tree, op: fn(DalekScalar, DalekScalar) -> DalekScalar| {
+            Some(op(l.try_evaluate()?, r.try_evaluate()?))
+        };
+        match self {
+            Tree::Zero => Some(DalekScalar::ZERO),
+            Tree::One => Some(DalekScalar::ONE),
+            Tree::Unnamed
+            | Tree::Identity
+            | Tree::Basepoint
+            | Tree::Name(_)
+            | Tree::Hash(..)
+            | Tree::Challenge(_)
+            | Tree::ScalarMul(..)
+            | Tree::Convert(..) => None,
+            Tree::Value(_, bytes) | Tree::FromBytes(bytes) => {
+                Some(DalekScalar::from_bytes_mod_order(*bytes))
+            }
+            Tree::Add(l, r) => binary(l, r, |l, r| l + r),
+            Tree::Sub(l, r) => binary(l, r, |l, r| l - r),
+            Tree::Mul(l, r) => binary(l, r, |l, r| l * r),
+            Tree::AddMany(xs) => xs.iter().map(Tree::try_evaluate).sum(),
+            Tree::MulMany(xs) => xs.iter().map(Tree::try_evaluate).product(),
+            Tree::Inv(x) => Some(x.try_evaluate()?.invert()),
+            Tree::Neg(x) => Some(-x.try_evaluate()?),
+            Tree::Pow(x, e) => Some(pow(x.try_evaluate()?, *e)),
+            Tree::Vartime(x) | Tree::Annotated(_, x) | Tree::Named(_, x) => x.try_evaluate(),
+            Tree::Byte(x, i) => x
+                .try_evaluate()?
+                .as_bytes()
+                .get(*i)
+                .copied()
+                .map(DalekScalar::from),
+            Tree::Clamp(x) => {
+                // clamp the original bytes, which may not be a canonical scalar
+                let bytes = match &**x {
+                    Tree::FromBytes(bytes) => *bytes,
+                    x => x.try_evaluate()?.to_bytes(),
+                };
+                Some(DalekScalar::from_bytes_mod_order(clamp_integer(bytes)))
+            }
+        }
+    }
+}
+
+/// `x^e`, by square-and-multiply
+fn pow(mut x: DalekScalar, mut e: u64) -> DalekScalar {
+    let mut acc = DalekScalar::ONE;
+    while e > 0 {
+        if e & 1 == 1 {
+            acc *= x;
+        }
+        x *= x;
+        e >>= 1;
+    }
+    acc
+}
+
+#[test]
+fn test_evaluate() {
+    use crate::{
+        scalar::{Scalar, TestScalar},
+        Named,
+    };
+
+    let a = TestScalar::from(2u8).named_with_value("a");
+    let b = TestScalar::from(3u8).named_with_value("b");
+    let x = (&a * &b - &a).invert() + &b;
+
+    assert_eq!(x.tree.evaluate(), x.value);
+    assert_eq!(
+        (&x * &TestScalar::from(5u8).named("c")).tree.try_evaluate(),
+        None
+    );
+}
+
+#[test]
+fn test_evaluate_byte_out_of_range() {
+    use crate::scalar::TestScalar;
+
+    let a = TestScalar::from(258u16).named_with_value("a");
+
+    assert_eq!(
+        Tree::Byte(Box::new(a.tree.clone()), 1).try_evaluate(),
+        Some(DalekScalar::ONE)
+    );
+    assert_eq!(Tree::Byte(Box::new(a.tree), 32).try_evaluate(), None);
+}
@@ -0,0 +1,108 @@
+//! Rendering of expression trees with their repeated subexpressions hoisted into bindings
+
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+
+use super::Tree;
+
+impl Tree {
+    /// Renders the expression like `{:?}`, binding each subexpression that occurs more than once
+    /// to a variable first
+    ///
+    /// Subexpressions are shared when they are structurally equal, and the bindings come before
+    /// the ones that use them, e.g. `let t0 = (a + b) in t0 * t0`. The variables are named `t0`,
+    /// `t1`, …, skipping the names already used by the leaves, in the order of a left-to-right
+    /// post-order traversal, so the output only depends on the structure of the expression.
+    pub fn render_with_lets(&self) -> String {
+        let mut counts = BTreeMap::new();
+        count_uses(self, &mut counts);
+        let mut shared = Vec::new();
+        collect_shared(self, &counts, &mut shared);
+
+        let used = self.variables();
+        let mut fresh = (0..)
+            .map(|i| format!("t{i}"))
+            .filter(|t| !used.contains(&t.as_str()));
+        let names: BTreeMap<&Tree, String> =
+            shared.iter().map(|&t| (t, fresh.next().unwrap())).collect();
+
+        let mut out = String::new();
+        for t in &shared {
+            let body = t.map_children(|c| substitute(c, &names));
+            out.push_str(&format!("let {} = {body:?} in ", names[t]));
+        }
+        out.push_str(&format!("{:?}", substitute(self, &names)));
+        out
+    }
+}
+
+/// Counts the occurrences of each subexpression, without looking inside repeated ones
+fn count_uses<'a>(tree: &'a Tree, counts: &mut BTreeMap<&'a Tree, usize>) {
+    let count = counts.entry(tree).or_default();
+    *count += 1;
+    if *count == 1 {
+        tree.children()
+            .into_iter()
+            .for_each(|c| count_uses(c, counts));
+    }
+}
+
+/// The non-leaf subexpressions used more than once, each after the ones it contains
+fn collect_shared<'a>(tree: &'a Tree, counts: &BTreeMap<&Tree, usize>, out: &mut Vec<&'a Tree>) {
+    if out.contains(&tree) {
+        return;
+    }
+    tree.children()
+        .into_iter()
+        .for_each(|c| collect_shared(c, counts, out));
+    if counts[tree] > 1 && !tree.children().is_empty() {
+        out.push(tree);
+    }
+}
+
+fn substitute(tree: &Tree, names: &BTreeMap<&Tree, String>) -> Tree {
+    match names.get(tree) {
+        Some(name) => Tree::Name(name.clone()),
+        None => tree.map_children(|c| substitute(c, names)),
+    }
+}
+
+#[test]
+fn test_render_with_lets() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let t0 = TestScalar::from(5u8).named("t0");
+
+    let s = &a + &b;
+    assert_eq!(
+        (&s * &s).tree.render_with_lets(),
+        "let t0 = (a + b) in t0 * t0"
+    );
+
+    let p = &s * &t0;
+    assert_eq!(
+        (&p - &p * &s).tree.render_with_lets(),
+        "let t1 = (a + b) in let t2 = t1 * t0 in (t2 - t2 * t1)"
+    );
+    assert_eq!(s.tree.render_with_lets(), "(a + b)");
+}
+
+#[test]
+fn test_render_with_lets_deterministic() {
+    use super::build::{add, inv, mul, name, sub};
+
+    let dag = || {
+        let x = add(name("a"), name("b"));
+        let y = mul(x.clone(), name("c"));
+        let z = inv(name("c"));
+        sub(mul(y.clone(), z.clone()), add(mul(z, x), y))
+    };
+
+    let rendered = dag().render_with_lets();
+    assert_eq!(rendered, dag().render_with_lets());
+    assert_eq!(
+        rendered,
+        "let t0 = (a + b) in let t1 = t0 * c in let t2 = c⁻¹ in (t1 * t2 - (t2 * t0 + t1))"
+    );
+}
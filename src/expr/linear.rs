@@ -0,0 +1,136 @@
+//! Recognition of linear combinations of named points
+
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+
+use super::Tree;
+
+impl Tree {
+    /// Decomposes a sum of `scalar · point` terms into its coefficients and the names of the points
+    ///
+    /// Subtracted and negated terms get a negated coefficient, and a bare named point has the
+    /// coefficient `1`. Returns `None` if some term isn't a multiple of a named leaf. Plain
+    /// products are accepted too, taking their right operand to be the point.
+    pub fn as_linear_combination(&self) -> Option<Vec<(Tree, String)>> {
+        match self {
+            Tree::Identity => Some(vec![]),
+            Tree::Name(p) | Tree::Value(p, _) | Tree::Named(p, _) => {
+                Some(vec![(Tree::One, p.clone())])
+            }
+            Tree::Basepoint => Some(vec![(Tree::One, "B".into())]),
+            Tree::Add(l, r) => {
+                let mut terms = l.as_linear_combination()?;
+                terms.extend(r.as_linear_combination()?);
+                Some(terms)
+            }
+            Tree::Sub(l, r) => {
+                let mut terms = l.as_linear_combination()?;
+                terms.extend(negated(r.as_linear_combination()?));
+                Some(terms)
+            }
+            Tree::Neg(x) => Some(negated(x.as_linear_combination()?)),
+            Tree::Vartime(x) | Tree::Annotated(_, x) => x.as_linear_combination(),
+            Tree::AddMany(xs) => xs.iter().try_fold(vec![], |mut terms, x| {
+                terms.extend(x.as_linear_combination()?);
+                Some(terms)
+            }),
+            Tree::Mul(c, x) | Tree::ScalarMul(c, x) => scaled((**c).clone(), x),
+            Tree::MulMany(xs) => match xs.as_slice() {
+                [] => None,
+                [x] => x.as_linear_combination(),
+                [c, x] => scaled(c.clone(), x),
+                [cs @ .., x] => scaled(Tree::MulMany(cs.to_vec()), x),
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether the expression has degree at most 1 in the named leaf `name`, the other leaves
+    /// being taken as constants
+    ///
+    /// Inverses, bytes and other non-polynomial functions of `name` are not affine, while any
+    /// function of the other leaves is: `(a * x + b)⁻¹` isn't affine in `x` but `a⁻¹ * x` is.
+    pub fn is_affine_in(&self, name: &str) -> bool {
+        self.degree_in(name).is_some_and(|d| d <= 1)
+    }
+
+    /// The degree of the expression as a polynomial in `name`, if it is one
+    fn degree_in(&self, name: &str) -> Option<u64> {
+        let degrees = || self.children().into_iter().map(|x| x.degree_in(name));
+        let highest = || degrees().try_fold(0, |d, x| Some(d.max(x?)));
+        match self {
+            Tree::Name(n) | Tree::Value(n, _) | Tree::Named(n, _) => Some((n == name).into()),
+            Tree::Add(..) | Tree::Sub(..) | Tree::AddMany(_) => highest(),
+            Tree::Mul(..) | Tree::ScalarMul(..) | Tree::MulMany(_) => {
+                degrees().try_fold(0u64, |d, x| d.checked_add(x?))
+            }
+            Tree::Neg(x) | Tree::Vartime(x) | Tree::Annotated(_, x) => x.degree_in(name),
+            Tree::Pow(x, e) => x.degree_in(name)?.checked_mul(*e),
+            _ => (highest()? == 0).then_some(0),
+        }
+    }
+}
+
+/// The terms of `c · x`
+fn scaled(c: Tree, x: &Tree) -> Option<Vec<(Tree, String)>> {
+    match x {
+        Tree::Name(p) | Tree::Value(p, _) | Tree::Named(p, _) => Some(vec![(c, p.clone())]),
+        Tree::Basepoint => Some(vec![(c, "B".into())]),
+        x => Some(
+            x.as_linear_combination()?
+                .into_iter()
+                .map(|(k, p)| (Tree::product_of(c.clone(), k), p))
+                .collect(),
+        ),
+    }
+}
+
+fn negated(terms: Vec<(Tree, String)>) -> Vec<(Tree, String)> {
+    terms
+        .into_iter()
+        .map(|(c, p)| (Tree::Neg(Box::new(c)), p))
+        .collect()
+}
+
+#[test]
+fn test_as_linear_combination() {
+    use crate::{
+        ristretto::{RistrettoPoint, TestRistrettoPoint},
+        scalar::TestScalar,
+        Named,
+    };
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&a).named("P");
+    let q = TestRistrettoPoint::mul_base(&b).named("Q");
+
+    let terms = (&a * &p + &b * &q).tree.as_linear_combination().unwrap();
+    assert_eq!(
+        terms,
+        [(a.tree.clone(), "P".into()), (b.tree.clone(), "Q".into())]
+    );
+
+    let terms = (&p - &q * &(&a * &b)).tree.as_linear_combination().unwrap();
+    assert_eq!(format!("{terms:?}"), r#"[(1, "P"), (-a * b, "Q")]"#);
+
+    let unnamed = TestRistrettoPoint::from(TestRistrettoPoint::mul_base(&b).value);
+    assert_eq!((&a * &p + unnamed).tree.as_linear_combination(), None);
+}
+
+#[test]
+fn test_is_affine_in() {
+    use crate::{
+        scalar::{Scalar, TestScalar},
+        Named,
+    };
+
+    let [a, b, x] = ["a", "b", "x"].map(|n| TestScalar::from(2u8).named(n));
+
+    assert!((&a * &x + &b).tree.is_affine_in("x"));
+    assert!(!(&x * &x).tree.is_affine_in("x"));
+    assert!((&x * &x).tree.is_affine_in("a"));
+    assert!((&a.invert() * &x - &(&a * &b)).tree.is_affine_in("x"));
+    assert!(!(&a * &x + &b).invert().tree.is_affine_in("x"));
+    assert!(!(&a * &x).square().tree.is_affine_in("x"));
+    assert!(b.square().tree.is_affine_in("x"));
+}
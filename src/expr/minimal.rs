@@ -0,0 +1,77 @@
+//! Rendering of expression trees with only the parentheses that precedence requires
+
+use alloc::{format, string::String};
+
+use super::{expand_subtraction, subtracted, Tree};
+
+impl Tree {
+    /// Renders the expression like `{:?}`, but only parenthesizes the operands that need it
+    ///
+    /// Operators are taken to group to the left, so `((a - b) - c)` renders as `a - b - c` while
+    /// `(a - (b - c))` keeps its parentheses. The right operand of a sum is parenthesized too, to
+    /// keep the shape of the tree visible.
+    pub fn render_minimal(&self) -> String {
+        infix(self, 0)
+    }
+}
+
+/// Renders the operands of an n-ary operator of precedence `prec`, grouping to the left
+fn operands(xs: &[Tree], op: &str, prec: u8) -> String {
+    let mut out = String::new();
+    for (i, x) in xs.iter().enumerate() {
+        let min = if i == 0 { prec } else { prec + 1 };
+        match subtracted(x) {
+            Some(x) if i > 0 && op == " + " => out.push_str(&format!(" - {}", infix(x, min))),
+            _ if i > 0 => out.push_str(&format!("{op}{}", infix(x, min))),
+            _ => out.push_str(&infix(x, min)),
+        }
+    }
+    out
+}
+
+/// Renders `tree`, parenthesized if its precedence is lower than `min`
+fn infix(tree: &Tree, min: u8) -> String {
+    let (prec, s) = match tree {
+        Tree::Add(l, r) => match subtracted(r) {
+            Some(r) => (1, format!("{} - {}", infix(l, 1), infix(r, 2))),
+            None => (1, format!("{} + {}", infix(l, 1), infix(r, 2))),
+        },
+        Tree::Sub(l, r) if expand_subtraction() => {
+            (1, format!("{} + -{}", infix(l, 1), infix(r, 3)))
+        }
+        Tree::Sub(l, r) => (1, format!("{} - {}", infix(l, 1), infix(r, 2))),
+        Tree::Mul(l, r) => (2, format!("{} * {}", infix(l, 2), infix(r, 3))),
+        Tree::ScalarMul(l, r) => (2, format!("{} · {}", infix(l, 2), infix(r, 3))),
+        Tree::AddMany(xs) if !xs.is_empty() => (1, operands(xs, " + ", 1)),
+        Tree::MulMany(xs) if !xs.is_empty() => (2, operands(xs, " * ", 2)),
+        Tree::Neg(x) => (3, format!("-{}", infix(x, 3))),
+        Tree::Inv(x) => (4, format!("{}⁻¹", infix(x, 5))),
+        Tree::Pow(x, e) => (4, format!("{}^{e}", infix(x, 5))),
+        Tree::Vartime(x) => (5, format!("⟂({})", infix(x, 0))),
+        Tree::Annotated(note, x) => return format!("/* {note} */ {}", infix(x, min)),
+        Tree::Byte(x, i) => (5, format!("{}[{i}]", infix(x, 5))),
+        Tree::Clamp(x) => (5, format!("clamp({})", infix(x, 0))),
+        Tree::Convert(model, x) => (5, format!("{model}({})", infix(x, 0))),
+        leaf => (5, format!("{leaf:?}")),
+    };
+    if prec < min {
+        format!("({s})")
+    } else {
+        s
+    }
+}
+
+#[test]
+fn test_render_minimal() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let c = TestScalar::from(5u8).named("c");
+
+    assert_eq!((&a - &b - &c).tree.render_minimal(), "a - b - c");
+    assert_eq!((&a - &(&b - &c)).tree.render_minimal(), "a - (b - c)");
+    assert_eq!((&(&a + &b) * &c).tree.render_minimal(), "(a + b) * c");
+    assert_eq!((&a * &b - &c).tree.render_minimal(), "a * b - c");
+    assert_eq!((-(&a * &b)).tree.render_minimal(), "-(a * b)");
+}
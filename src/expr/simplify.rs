@@ -0,0 +1,231 @@
+//! Syntactic simplification of expression trees
+
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use super::{
+    compare::{split_add, split_mul, Split},
+    Tree,
+};
+
+impl Tree {
+    /// Rewrites the expression bottom-up with purely syntactic folding rules
+    ///
+    /// The rules never change the value of the expression:
+    /// - `--x` becomes `x`
+    /// - `-0` becomes `0`
+    /// - `(x⁻¹)⁻¹` becomes `x`
+    /// - `(x^a)^b` becomes `x^(a * b)`, so repeated squarings show up as a power of two
+    /// - `x` and `-x` cancel out when they are both addends of the same sum
+    ///
+    /// Subtractions are left alone; rewrite them with [`Tree::normalize_sub`] first so that they
+    /// take part in the cancellations.
+    pub fn simplify(&self) -> Tree {
+        match self {
+            Tree::Inv(x) => fold_inv(x.simplify()),
+            Tree::Neg(x) => fold_neg(x.simplify()),
+            Tree::Pow(x, e) => fold_pow(x.simplify(), *e),
+            Tree::Add(..) | Tree::AddMany(_) => cancel_opposites(self),
+            _ => self.map_children(Tree::simplify),
+        }
+    }
+
+    /// Rewrites every subtraction `a - b` as the sum `a + -b`
+    pub fn normalize_sub(&self) -> Tree {
+        match self {
+            Tree::Sub(l, r) => Tree::Add(
+                Box::new(l.normalize_sub()),
+                Box::new(Tree::Neg(Box::new(r.normalize_sub()))),
+            ),
+            _ => self.map_children(Tree::normalize_sub),
+        }
+    }
+
+    /// Rewrites nested sums and products, binary or not, into single n-ary nodes
+    ///
+    /// The operands stay in order, so `(a + b) + c` and `a + (b + c)` both become `a + b + c`.
+    pub fn flatten(&self) -> Tree {
+        match self {
+            Tree::Add(..) | Tree::AddMany(_) => {
+                Tree::AddMany(flat_operands(self, split_add, Tree::flatten))
+            }
+            Tree::Mul(..) | Tree::MulMany(_) => {
+                Tree::MulMany(flat_operands(self, split_mul, Tree::flatten))
+            }
+            _ => self.map_children(Tree::flatten),
+        }
+    }
+}
+
+/// The operands of the chain of nodes that `split` breaks apart, each rewritten with `f`
+fn flat_operands(tree: &Tree, split: Split, f: fn(&Tree) -> Tree) -> Vec<Tree> {
+    match split(tree) {
+        Some(operands) => operands
+            .into_iter()
+            .flat_map(|x| flat_operands(x, split, f))
+            .collect(),
+        None => vec![f(tree)],
+    }
+}
+
+/// Simplifies a sum, removing the pairs of opposite addends
+///
+/// The sum keeps its shape unless something cancels out; the remaining addends are then summed
+/// from left to right.
+fn cancel_opposites(sum: &Tree) -> Tree {
+    let mut addends = flat_operands(sum, split_add, Tree::simplify);
+    let count = addends.len();
+    let mut i = 0;
+    while i < addends.len() {
+        match addends[i + 1..]
+            .iter()
+            .position(|x| opposite(&addends[i], x))
+        {
+            Some(j) => {
+                addends.remove(i + 1 + j);
+                addends.remove(i);
+            }
+            None => i += 1,
+        }
+    }
+    if addends.len() == count {
+        return sum.map_children(Tree::simplify);
+    }
+    addends
+        .into_iter()
+        .reduce(|l, r| Tree::Add(Box::new(l), Box::new(r)))
+        .unwrap_or(Tree::Zero)
+}
+
+fn opposite(x: &Tree, y: &Tree) -> bool {
+    match (x, y) {
+        (Tree::Neg(x), y) | (y, Tree::Neg(x)) => **x == *y,
+        _ => false,
+    }
+}
+
+fn fold_neg(x: Tree) -> Tree {
+    match x {
+        Tree::Neg(x) => *x,
+        Tree::Zero => Tree::Zero,
+        x => Tree::Neg(Box::new(x)),
+    }
+}
+
+fn fold_inv(x: Tree) -> Tree {
+    match x {
+        Tree::Inv(x) => *x,
+        x => Tree::Inv(Box::new(x)),
+    }
+}
+
+fn fold_pow(x: Tree, e: u64) -> Tree {
+    match x {
+        Tree::Pow(x, f) if f.checked_mul(e).is_some() => Tree::Pow(x, f * e),
+        x => Tree::Pow(Box::new(x), e),
+    }
+}
+
+#[test]
+fn test_fold_neg() {
+    use crate::{
+        scalar::{Scalar, TestScalar},
+        Named,
+    };
+
+    let a = TestScalar::from(2u8).named("a");
+
+    assert_eq!(format!("{:?}", (-(-&a)).tree.simplify()), "a");
+    assert_eq!(format!("{:?}", (-&a).tree.simplify()), "-a");
+    assert_eq!(format!("{:?}", (-TestScalar::ZERO).tree.simplify()), "0");
+}
+
+#[test]
+fn test_fold_inv() {
+    use crate::{
+        scalar::{Scalar, TestScalar},
+        Named,
+    };
+
+    let a = TestScalar::from(2u8).named("a");
+
+    assert_eq!(format!("{:?}", a.invert().invert().tree.simplify()), "a");
+    assert_eq!(format!("{:?}", a.invert().tree.simplify()), "a⁻¹");
+}
+
+#[test]
+fn test_fold_pow() {
+    use super::Gate;
+    use crate::scalar::TestScalar;
+
+    let x = TestScalar::from(3u8).named_with_value("x");
+    let y = x.square().square();
+
+    assert_eq!(format!("{:?}", y.tree), "(x^2)^2");
+    assert_eq!(format!("{:?}", y.tree.simplify()), "x^4");
+    assert_eq!(y.tree.simplify().evaluate(), y.value);
+    assert_eq!(y.value, x.value * x.value * x.value * x.value);
+    assert_eq!(
+        y.tree.simplify().to_gates(),
+        [
+            Gate::Input(x.tree.clone()),
+            Gate::Mul(0, 0),
+            Gate::Mul(1, 1)
+        ]
+    );
+    assert_eq!(
+        format!("{:?}", (&x * &x).square().square().square().tree.simplify()),
+        "(x * x)^8"
+    );
+}
+
+#[test]
+fn test_flatten() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let c = TestScalar::from(5u8).named("c");
+
+    let sum = (&a + &b + (&c + &a * &b * &c)).tree.flatten();
+    let Tree::AddMany(terms) = &sum else {
+        panic!("expected an n-ary sum, got {sum:#?}");
+    };
+    assert_eq!(terms.len(), 4);
+    assert!(matches!(&terms[3], Tree::MulMany(factors) if factors.len() == 3));
+    assert_eq!(format!("{sum:?}"), "(a + b + c + a * b * c)");
+    assert_eq!(
+        sum.canonicalize(),
+        (&a + &b + &c + &a * &b * &c).tree.canonicalize()
+    );
+    assert_eq!(sum.term_count(), 4);
+    assert_eq!(sum.flatten(), sum);
+    assert_eq!(format!("{:?}", (-(&a * &b)).tree.flatten()), "-a * b");
+}
+
+#[test]
+fn test_normalize_sub() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let c = TestScalar::from(5u8).named("c");
+
+    let x = (&a - &b) + &b;
+    let leaf = |x: &TestScalar| Box::new(x.tree.clone());
+    assert_eq!(
+        x.tree.normalize_sub(),
+        Tree::Add(
+            Box::new(Tree::Add(leaf(&a), Box::new(Tree::Neg(leaf(&b))))),
+            leaf(&b)
+        )
+    );
+    assert_eq!(format!("{:?}", x.tree.simplify()), "((a - b) + b)");
+    assert_eq!(x.tree.normalize_sub().simplify(), a.tree);
+
+    let y = &c + &a - &b + (&b - &a);
+    assert_eq!(y.tree.normalize_sub().simplify(), c.tree);
+    assert_eq!(
+        format!("{:?}", (&b - &b).tree.normalize_sub().simplify()),
+        "0"
+    );
+}
@@ -0,0 +1,177 @@
+//! SMT-LIB export of expression trees
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Write;
+
+use super::Tree;
+
+/// The order of the Ristretto group, i.e. the modulus of the scalar field
+const GROUP_ORDER: &str =
+    "7237005577332262213973186563042994240857116359379907606001950938285454250989";
+
+impl Tree {
+    /// Renders the expression as an SMT-LIB script over the integers modulo the group order
+    ///
+    /// Named leaves become declared constants constrained to the range `[0, l)`, unnamed leaves
+    /// get fresh constants, challenges get constants prefixed with `chal!` so that they can't be
+    /// mistaken for a leaf of the same name, and each inversion introduces a constant asserted to
+    /// be the inverse of its operand. The basepoint is the constant `basepoint!`; like the other
+    /// generated constants, its name contains a `!`, which leaf names shouldn't use. The
+    /// expression itself is bound to `expr`, reduced modulo `l`, so that two exported expressions
+    /// can be compared with `(assert (not (= expr1 expr2)))`.
+    pub fn to_smtlib(&self) -> String {
+        let mut smt = Smt::default();
+        let term = smt.term(self);
+
+        let mut out = String::new();
+        out.push_str("(set-logic QF_NIA)\n");
+        writeln!(out, "(define-fun l () Int {GROUP_ORDER})").unwrap();
+        for c in &smt.consts {
+            writeln!(out, "(declare-const {c} Int)").unwrap();
+            writeln!(out, "(assert (and (<= 0 {c}) (< {c} l)))").unwrap();
+        }
+        for a in &smt.asserts {
+            writeln!(out, "(assert {a})").unwrap();
+        }
+        writeln!(out, "(define-fun expr () Int (mod {term} l))").unwrap();
+        out
+    }
+}
+
+#[derive(Default)]
+struct Smt {
+    consts: Vec<String>,
+    asserts: Vec<String>,
+    fresh: usize,
+}
+
+impl Smt {
+    fn declare(&mut self, name: String) -> String {
+        if !self.consts.contains(&name) {
+            self.consts.push(name.clone());
+        }
+        name
+    }
+
+    fn fresh(&mut self, prefix: &str) -> String {
+        let name = format!("{prefix}!{}", self.fresh);
+        self.fresh += 1;
+        self.declare(name)
+    }
+
+    /// Applies `op` to the terms of `operands`, which may be fewer than two
+    fn nary(&mut self, op: &str, operands: &[Tree], empty: &str) -> String {
+        let terms: Vec<_> = operands.iter().map(|x| self.term(x)).collect();
+        match terms.len() {
+            0 => empty.into(),
+            1 => terms.into_iter().next().unwrap(),
+            _ => format!("({op} {})", terms.join(" ")),
+        }
+    }
+
+    /// `base^e` by square-and-multiply, with a constant for each square so that the terms stay
+    /// small for large exponents
+    fn pow(&mut self, mut base: String, mut e: u64) -> String {
+        let mut factors = Vec::new();
+        while e > 0 {
+            if e & 1 == 1 {
+                factors.push(base.clone());
+            }
+            e >>= 1;
+            if e > 0 {
+                let square = self.fresh("pow");
+                self.asserts
+                    .push(format!("(= {square} (mod (* {base} {base}) l))"));
+                base = square;
+            }
+        }
+        match factors.len() {
+            0 => "1".into(),
+            1 => factors.pop().unwrap(),
+            _ => format!("(* {})", factors.join(" ")),
+        }
+    }
+
+    fn term(&mut self, tree: &Tree) -> String {
+        match tree {
+            Tree::Zero | Tree::Identity => "0".into(),
+            Tree::One => "1".into(),
+            Tree::Unnamed => self.fresh("unnamed"),
+            Tree::Hash(..) => self.fresh("hash"),
+            Tree::Basepoint => self.declare("basepoint!".into()),
+            Tree::Clamp(_) => self.fresh("clamp"),
+            Tree::Convert(..) => self.fresh("convert"),
+            Tree::FromBytes(_) => self.fresh("bytes"),
+            Tree::Name(s) | Tree::Value(s, _) | Tree::Named(s, _) => self.declare(symbol(s)),
+            Tree::Challenge(s) => self.declare(symbol(&format!("chal!{s}"))),
+            Tree::Add(l, r) => format!("(+ {} {})", self.term(l), self.term(r)),
+            Tree::Sub(l, r) => format!("(- {} {})", self.term(l), self.term(r)),
+            Tree::Mul(l, r) | Tree::ScalarMul(l, r) => {
+                format!("(* {} {})", self.term(l), self.term(r))
+            }
+            Tree::AddMany(xs) => self.nary("+", xs, "0"),
+            Tree::MulMany(xs) => self.nary("*", xs, "1"),
+            Tree::Neg(x) => format!("(- {})", self.term(x)),
+            Tree::Vartime(x) | Tree::Annotated(_, x) => self.term(x),
+            Tree::Byte(x, i) => {
+                let shifted = (0..*i).fold(format!("(mod {} l)", self.term(x)), |t, _| {
+                    format!("(div {t} 256)")
+                });
+                format!("(mod {shifted} 256)")
+            }
+            Tree::Pow(x, e) => {
+                let x = self.term(x);
+                self.pow(x, *e)
+            }
+            Tree::Inv(x) => {
+                let x = self.term(x);
+                let inv = self.fresh("inv");
+                self.asserts.push(format!("(= (mod (* {x} {inv}) l) 1)"));
+                inv
+            }
+        }
+    }
+}
+
+/// Quotes a name unless it is already a valid simple SMT-LIB symbol
+fn symbol(name: &str) -> String {
+    let simple = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if simple {
+        name.into()
+    } else {
+        format!("|{name}|")
+    }
+}
+
+#[test]
+fn test_to_smtlib() {
+    let a = Tree::Name("a".into());
+    let b = Tree::Name("b".into());
+    let smt = Tree::Add(Box::new(a), Box::new(b)).to_smtlib();
+
+    assert!(smt.contains("(declare-const a Int)"));
+    assert!(smt.contains("(declare-const b Int)"));
+    assert!(smt.contains("(define-fun expr () Int (mod (+ a b) l))"));
+}
+
+#[test]
+fn test_to_smtlib_challenge() {
+    let c = Tree::Name("c".into());
+    let chal = Tree::Challenge("c".into());
+    let smt = Tree::Mul(Box::new(c), Box::new(chal)).to_smtlib();
+
+    assert!(smt.contains("(declare-const c Int)"));
+    assert!(smt.contains("(declare-const |chal!c| Int)"));
+    assert!(smt.contains("(define-fun expr () Int (mod (* c |chal!c|) l))"));
+}
+
+#[test]
+fn test_to_smtlib_basepoint() {
+    let b = Tree::Name("B".into());
+    let smt = Tree::Add(Box::new(b), Box::new(Tree::Basepoint)).to_smtlib();
+
+    assert!(smt.contains("(declare-const B Int)"));
+    assert!(smt.contains("(declare-const basepoint! Int)"));
+    assert!(smt.contains("(define-fun expr () Int (mod (+ B basepoint!) l))"));
+}
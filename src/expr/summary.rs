@@ -0,0 +1,91 @@
+//! One-line statistics about expression trees too large to read
+
+use alloc::{format, string::String};
+
+use super::Tree;
+
+/// The kinds of operations counted by [`Tree::summary`], in the order they are reported
+const OPS: [&str; 12] = [
+    "add",
+    "sub",
+    "mul",
+    "scalar_mul",
+    "inv",
+    "neg",
+    "pow",
+    "vartime",
+    "byte",
+    "clamp",
+    "annotated",
+    "convert",
+];
+
+impl Tree {
+    /// Summarizes the expression in one line, e.g.
+    /// `expr[nodes=1234, depth=57, vars=5, add=634, mul=600]`
+    ///
+    /// Besides the number of nodes, the depth and the number of distinct named leaves, it counts
+    /// the nodes of each kind of operation that occurs. An n-ary sum or product counts as a single
+    /// node.
+    pub fn summary(&self) -> String {
+        let mut nodes = 0;
+        let mut counts = [0usize; OPS.len()];
+        self.count_nodes(&mut nodes, &mut counts);
+
+        let mut out = format!(
+            "expr[nodes={nodes}, depth={}, vars={}",
+            self.depth(),
+            self.num_variables()
+        );
+        for (op, count) in OPS.iter().zip(counts).filter(|&(_, count)| count > 0) {
+            out.push_str(&format!(", {op}={count}"));
+        }
+        out.push(']');
+        out
+    }
+
+    fn count_nodes(&self, nodes: &mut usize, counts: &mut [usize; OPS.len()]) {
+        *nodes += 1;
+        let op = match self {
+            Tree::Add(..) | Tree::AddMany(_) => Some("add"),
+            Tree::Sub(..) => Some("sub"),
+            Tree::Mul(..) | Tree::MulMany(_) => Some("mul"),
+            Tree::ScalarMul(..) => Some("scalar_mul"),
+            Tree::Inv(_) => Some("inv"),
+            Tree::Neg(_) => Some("neg"),
+            Tree::Pow(..) => Some("pow"),
+            Tree::Vartime(_) => Some("vartime"),
+            Tree::Byte(..) => Some("byte"),
+            Tree::Clamp(_) => Some("clamp"),
+            Tree::Annotated(..) => Some("annotated"),
+            Tree::Convert(..) => Some("convert"),
+            _ => None,
+        };
+        if let Some(i) = op.and_then(|op| OPS.iter().position(|&o| o == op)) {
+            counts[i] += 1;
+        }
+        for c in self.children() {
+            c.count_nodes(nodes, counts);
+        }
+    }
+}
+
+#[test]
+fn test_summary() {
+    use crate::{
+        ristretto::{RistrettoPoint, TestRistrettoPoint},
+        scalar::TestScalar,
+        Named,
+    };
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&a).named("P");
+
+    let x = &(&a * &b + &a * &a - &b) * &p + -&p;
+    assert_eq!(
+        x.tree.summary(),
+        "expr[nodes=14, depth=6, vars=3, add=2, sub=1, mul=2, scalar_mul=1, neg=1]"
+    );
+    assert_eq!(a.tree.summary(), "expr[nodes=1, depth=1, vars=1]");
+}
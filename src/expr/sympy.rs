@@ -0,0 +1,236 @@
+//! Export of expression trees as Python code for sympy
+
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+use core::fmt::Write;
+
+use super::Tree;
+
+impl Tree {
+    /// Renders the expression as a Python snippet declaring its symbols with sympy and binding
+    /// the expression to `expr`
+    ///
+    /// Names that aren't valid Python identifiers keep their name as a sympy symbol but are bound
+    /// to a sanitized variable. Unnamed leaves become fresh symbols `_0`, `_1`, …, and opaque
+    /// leaves like bytes, clamped scalars or conversions become fresh symbols such as `byte0`,
+    /// `clamp0` or `convert0`. Challenges become symbols prefixed with `chal_`.
+    pub fn to_sympy(&self) -> String {
+        let mut py = Sympy::default();
+        let expr = py.expr(self, 0);
+
+        let mut out = String::from("from sympy import symbols\n");
+        if !py.symbols.is_empty() {
+            let vars: Vec<_> = py.symbols.iter().map(|(var, _)| var.as_str()).collect();
+            let names: Vec<_> = py.symbols.iter().map(|(_, name)| quoted(name)).collect();
+            // trailing commas on both sides keep a single symbol in a tuple
+            writeln!(
+                out,
+                "{}, = symbols('{},')",
+                vars.join(", "),
+                names.join(" ")
+            )
+            .unwrap();
+        }
+        writeln!(out, "expr = {expr}").unwrap();
+        out
+    }
+}
+
+/// Escapes `name` to be read back as a single symbol by `symbols('…')`
+///
+/// sympy splits names at whitespace and commas, and expands colons into ranges, unless they are
+/// escaped with a backslash; backslashes and quotes are then escaped for the Python string.
+fn quoted(name: &str) -> String {
+    let mut out = String::new();
+    for c in name.chars() {
+        if c.is_whitespace() || c == ',' || c == ':' {
+            out.push_str("\\\\");
+        } else if c == '\\' || c == '\'' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// The reserved words of Python, which can't be variables
+const KEYWORDS: [&str; 35] = [
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+/// Sanitizes `name` into a Python identifier
+///
+/// Characters other than ASCII letters and digits become `_`, a leading digit gets a `_` in
+/// front of it and keywords get a `_` after them.
+fn identifier(name: &str) -> String {
+    let var: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if var.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{var}")
+    } else if KEYWORDS.contains(&var.as_str()) {
+        format!("{var}_")
+    } else {
+        var
+    }
+}
+
+#[derive(Default)]
+struct Sympy {
+    /// Python variable and sympy symbol name of each leaf
+    symbols: Vec<(String, String)>,
+    /// Next fresh index for each prefix
+    fresh: BTreeMap<&'static str, usize>,
+}
+
+impl Sympy {
+    /// The variable bound to the symbol `name`, declaring it if needed
+    ///
+    /// Names that sanitize to the variable of another name get a numeric suffix, e.g. `a b` and
+    /// `a_b` become `a_b` and `a_b_2`.
+    fn symbol(&mut self, name: &str) -> String {
+        if let Some((var, _)) = self.symbols.iter().find(|(_, n)| n == name) {
+            return var.clone();
+        }
+        let base = identifier(name);
+        let mut var = base.clone();
+        let mut suffix = 1;
+        while self.symbols.iter().any(|(v, _)| *v == var) {
+            suffix += 1;
+            var = format!("{base}_{suffix}");
+        }
+        self.symbols.push((var.clone(), name.into()));
+        var
+    }
+
+    /// Declares a new symbol `{prefix}{n}` that stands for a leaf with no usable name
+    fn fresh(&mut self, prefix: &'static str) -> String {
+        loop {
+            let n = self.fresh.entry(prefix).or_default();
+            let name = format!("{prefix}{n}");
+            *n += 1;
+            if !self.symbols.iter().any(|(_, taken)| *taken == name) {
+                return self.symbol(&name);
+            }
+        }
+    }
+
+    /// Renders the operands of an n-ary operator of precedence `prec`, grouping to the left
+    fn operands(&mut self, operands: &[Tree], op: &str, prec: u8) -> String {
+        let rendered: Vec<_> = operands
+            .iter()
+            .enumerate()
+            .map(|(i, x)| self.expr(x, if i == 0 { prec } else { prec + 1 }))
+            .collect();
+        rendered.join(op)
+    }
+
+    /// Renders `tree`, parenthesized if its precedence is lower than `min`
+    fn expr(&mut self, tree: &Tree, min: u8) -> String {
+        let (prec, s) = match tree {
+            Tree::Zero | Tree::Identity => (5, "0".into()),
+            Tree::One => (5, "1".into()),
+            Tree::Unnamed | Tree::Hash(..) | Tree::FromBytes(_) => (5, self.fresh("_")),
+            Tree::Name(s) | Tree::Value(s, _) | Tree::Named(s, _) => (5, self.symbol(s)),
+            Tree::Add(l, r) => (1, format!("{} + {}", self.expr(l, 1), self.expr(r, 2))),
+            Tree::Sub(l, r) => (1, format!("{} - {}", self.expr(l, 1), self.expr(r, 2))),
+            Tree::Mul(l, r) | Tree::ScalarMul(l, r) => {
+                (2, format!("{}*{}", self.expr(l, 2), self.expr(r, 3)))
+            }
+            Tree::AddMany(xs) if xs.is_empty() => (5, "0".into()),
+            Tree::AddMany(xs) => (1, self.operands(xs, " + ", 1)),
+            Tree::MulMany(xs) if xs.is_empty() => (5, "1".into()),
+            Tree::MulMany(xs) => (2, self.operands(xs, "*", 2)),
+            Tree::Neg(x) => (2, format!("-{}", self.expr(x, 3))),
+            Tree::Inv(x) => (4, format!("{}**-1", self.expr(x, 5))),
+            Tree::Pow(x, e) => (4, format!("{}**{e}", self.expr(x, 5))),
+            Tree::Vartime(x) | Tree::Annotated(_, x) => return self.expr(x, min),
+            Tree::Byte(..) => (5, self.fresh("byte")),
+            Tree::Clamp(_) => (5, self.fresh("clamp")),
+            Tree::Convert(..) => (5, self.fresh("convert")),
+            Tree::Challenge(label) => (5, self.symbol(&identifier(&format!("chal_{label}")))),
+            Tree::Basepoint => (5, self.symbol("B")),
+        };
+        if prec < min {
+            format!("({s})")
+        } else {
+            s
+        }
+    }
+}
+
+#[test]
+fn test_to_sympy() {
+    use crate::{
+        scalar::{Scalar, TestScalar},
+        Named,
+    };
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let c = TestScalar::from(5u8).named("c");
+    let py = ((&a + &b) * c.invert()).tree.to_sympy();
+
+    assert_eq!(
+        py,
+        "from sympy import symbols\na, b, c, = symbols('a b c,')\nexpr = (a + b)*c**-1\n"
+    );
+}
+
+#[test]
+fn test_to_sympy_opaque_leaves() {
+    use alloc::boxed::Box;
+
+    let a = Box::new(Tree::Name("a".into()));
+    let tree = Tree::Add(
+        Box::new(Tree::Byte(a.clone(), 3)),
+        Box::new(Tree::Mul(
+            Box::new(Tree::Byte(a.clone(), 4)),
+            Box::new(Tree::Clamp(a.clone())),
+        )),
+    );
+    let tree = Tree::Sub(Box::new(tree), Box::new(Tree::Convert("edwards".into(), a)));
+    let challenge = Box::new(Tree::Challenge("c 1".into()));
+    let tree = Tree::Mul(
+        Box::new(tree),
+        Box::new(Tree::Add(challenge.clone(), challenge)),
+    );
+
+    assert_eq!(
+        tree.to_sympy(),
+        "from sympy import symbols\nbyte0, byte1, clamp0, convert0, chal_c_1, = symbols('byte0 byte1 clamp0 convert0 chal_c_1,')\nexpr = (byte0 + byte1*clamp0 - convert0)*(chal_c_1 + chal_c_1)\n"
+    );
+}
+
+#[test]
+fn test_to_sympy_sanitized_names() {
+    use alloc::boxed::Box;
+
+    let name = |s: &str| Box::new(Tree::Name(s.into()));
+    let tree = Tree::MulMany(vec![
+        Tree::Add(name("a b"), name("a_b")),
+        Tree::Sub(name("lambda"), name("2x")),
+        *name("a b"),
+    ]);
+
+    assert_eq!(
+        tree.to_sympy(),
+        "from sympy import symbols\na_b, a_b_2, lambda_, _2x, = symbols('a\\\\ b a_b lambda 2x,')\nexpr = (a_b + a_b_2)*(lambda_ - _2x)*a_b\n"
+    );
+}
+
+#[test]
+fn test_to_sympy_single_symbol() {
+    use alloc::boxed::Box;
+
+    let a = Box::new(Tree::Name("a".into()));
+    let tree = Tree::Mul(a.clone(), Box::new(Tree::Add(a, Box::new(Tree::One))));
+
+    assert_eq!(
+        tree.to_sympy(),
+        "from sympy import symbols\na, = symbols('a,')\nexpr = a*(a + 1)\n"
+    );
+}
@@ -0,0 +1,145 @@
+//! Rendering of expression trees reflowed to a maximum width
+
+use alloc::{format, string::String, vec, vec::Vec};
+
+use super::{expand_subtraction, subtracted, Tree};
+
+impl Tree {
+    /// Renders the expression like `{:?}`, breaking lines before operators so that no line is
+    /// wider than `width` characters
+    ///
+    /// Subexpressions that fit are kept on one line, the others put their second operand on a new
+    /// line, indented to line up with the first one. Nothing is elided, so names wider than the
+    /// available space still overflow it.
+    pub fn render_wrapped(&self, width: usize) -> String {
+        layout(self, 0, 0, width).join("\n")
+    }
+}
+
+/// Lays out `tree` starting at column `col`, leaving room for `trail` characters after it
+///
+/// The first line only holds the text of the subexpression, the following ones are indented.
+fn layout(tree: &Tree, col: usize, trail: usize, width: usize) -> Vec<String> {
+    let flat = format!("{tree:?}");
+    if col + flat.chars().count() + trail <= width {
+        return vec![flat];
+    }
+    match tree {
+        Tree::Add(l, r) => match subtracted(r) {
+            Some(r) => binary(l, "-", r, col, trail, width),
+            None => binary(l, "+", r, col, trail, width),
+        },
+        Tree::Sub(l, r) if expand_subtraction() => {
+            binary(l, "+", &Tree::Neg(r.clone()), col, trail, width)
+        }
+        Tree::Sub(l, r) => binary(l, "-", r, col, trail, width),
+        Tree::Mul(l, r) => {
+            let mut lines = layout(l, col, 0, width);
+            lines.extend(operand("*", r, col, trail, width));
+            lines
+        }
+        Tree::ScalarMul(l, r) => {
+            let mut lines = layout(l, col, 0, width);
+            lines.extend(operand("·", r, col, trail, width));
+            lines
+        }
+        Tree::AddMany(xs) if !xs.is_empty() => {
+            let mut lines = chain(xs, "+", col + 1, trail + 1, width);
+            lines[0].insert(0, '(');
+            lines.last_mut().unwrap().push(')');
+            lines
+        }
+        Tree::MulMany(xs) if !xs.is_empty() => chain(xs, "*", col, trail, width),
+        Tree::Inv(x) => {
+            let mut lines = layout(x, col, trail + 2, width);
+            lines.last_mut().unwrap().push_str("⁻¹");
+            lines
+        }
+        Tree::Pow(x, e) => match **x {
+            Tree::Mul(..) | Tree::MulMany(_) | Tree::Inv(_) | Tree::Neg(_) | Tree::Pow(..) => {
+                prefixed("(", x, &format!(")^{e}"), col, trail, width)
+            }
+            _ => {
+                let power = format!("^{e}");
+                let mut lines = layout(x, col, trail + power.chars().count(), width);
+                lines.last_mut().unwrap().push_str(&power);
+                lines
+            }
+        },
+        Tree::Neg(x) => prefixed("-", x, "", col, trail, width),
+        Tree::Vartime(x) => prefixed("⟂(", x, ")", col, trail, width),
+        Tree::Annotated(note, x) => prefixed(&format!("/* {note} */ "), x, "", col, trail, width),
+        Tree::Clamp(x) => prefixed("clamp(", x, ")", col, trail, width),
+        Tree::Convert(model, x) => prefixed(&format!("{model}("), x, ")", col, trail, width),
+        _ => vec![flat],
+    }
+}
+
+/// The lines of a parenthesized binary operation
+fn binary(l: &Tree, op: &str, r: &Tree, col: usize, trail: usize, width: usize) -> Vec<String> {
+    let mut lines = layout(l, col + 1, 0, width);
+    lines[0].insert(0, '(');
+    lines.extend(operand(op, r, col + 1, trail + 1, width));
+    lines.last_mut().unwrap().push(')');
+    lines
+}
+
+/// The lines of the operands of an n-ary operation, without parentheses
+fn chain(operands: &[Tree], op: &str, col: usize, trail: usize, width: usize) -> Vec<String> {
+    let last = operands.len() - 1;
+    let trail_of = |i| if i == last { trail } else { 0 };
+    let mut lines = layout(&operands[0], col, trail_of(0), width);
+    for (i, x) in operands.iter().enumerate().skip(1) {
+        lines.extend(match subtracted(x) {
+            Some(x) if op == "+" => operand("-", x, col, trail_of(i), width),
+            _ => operand(op, x, col, trail_of(i), width),
+        });
+    }
+    lines
+}
+
+/// The lines of the operand `x` of `op`, which starts a new line at column `col`
+fn operand(op: &str, x: &Tree, col: usize, trail: usize, width: usize) -> Vec<String> {
+    let indent = " ".repeat(col);
+    let mut lines = layout(x, col + op.chars().count() + 1, trail, width);
+    lines[0] = format!("{indent}{op} {}", lines[0]);
+    lines
+}
+
+/// The lines of a unary operation written around its operand
+fn prefixed(
+    open: &str,
+    x: &Tree,
+    close: &str,
+    col: usize,
+    trail: usize,
+    width: usize,
+) -> Vec<String> {
+    let n = close.chars().count();
+    let mut lines = layout(x, col + open.chars().count(), trail + n, width);
+    lines[0].insert_str(0, open);
+    lines.last_mut().unwrap().push_str(close);
+    lines
+}
+
+#[test]
+fn test_render_wrapped() {
+    use crate::{scalar::TestScalar, Named};
+
+    let terms: Vec<_> = (0..16)
+        .map(|i| TestScalar::from(i as u8).named(format!("term{i}")))
+        .collect();
+    let sum = terms.iter().skip(1).fold(terms[0].clone(), |s, t| &s + t);
+    let wide = &sum * &terms[3];
+
+    let wrapped = wide.tree.render_wrapped(40);
+    assert!(wrapped.lines().count() > 1);
+    assert!(wrapped.lines().all(|l| l.chars().count() <= 40));
+    assert_eq!(
+        wrapped.split_whitespace().collect::<String>(),
+        format!("{:?}", wide.tree)
+            .split_whitespace()
+            .collect::<String>()
+    );
+    assert_eq!(terms[1].tree.render_wrapped(40), "term1");
+}
@@ -0,0 +1,97 @@
+//! Fiat–Shamir transcripts whose challenges are labeled in the expression trees
+
+use alloc::{format, string::String};
+
+use digest::{typenum::U64, Digest};
+
+use crate::{
+    scalar::TestScalar,
+    tracked::{Encoding, Tracked},
+};
+
+/// A hash of the messages of a protocol, squeezed into challenges labeled `label#0`, `label#1`, …
+///
+/// Each message and each challenge is absorbed with its label, so the challenges depend on
+/// everything that came before them. This only mimics the shape of a real transcript and is not
+/// meant to be secure.
+pub struct FiatShamir<D> {
+    hash: D,
+    label: String,
+    challenges: usize,
+}
+
+impl<D> FiatShamir<D>
+where
+    D: Digest<OutputSize = U64> + Clone,
+{
+    /// Starts the transcript of a protocol, whose name also labels its challenges
+    pub fn new<S>(label: S) -> Self
+    where
+        String: From<S>,
+    {
+        let label = String::from(label);
+        let mut hash = D::new();
+        absorb_labeled(&mut hash, "protocol", label.as_bytes());
+        Self {
+            hash,
+            label,
+            challenges: 0,
+        }
+    }
+
+    /// Appends a point or a scalar to the transcript, by its canonical encoding
+    pub fn absorb<T: Encoding>(&mut self, label: &str, value: &Tracked<T>) {
+        absorb_labeled(&mut self.hash, label, &value.value.encoding());
+    }
+
+    /// Derives the next challenge from everything absorbed so far
+    pub fn challenge(&mut self) -> TestScalar {
+        let name = format!("{}#{}", self.label, self.challenges);
+        self.challenges += 1;
+        let mut hash = self.hash.clone();
+        absorb_labeled(&mut hash, "challenge", name.as_bytes());
+        let mut bytes = [0; 64];
+        bytes.copy_from_slice(&hash.finalize());
+        let challenge = TestScalar::challenge(name, &bytes);
+        absorb_labeled(&mut self.hash, "challenge", &challenge.value.to_bytes());
+        challenge
+    }
+}
+
+/// Appends the length-prefixed label and message, so that different splits can't collide
+fn absorb_labeled<D: Digest>(hash: &mut D, label: &str, message: &[u8]) {
+    for part in [label.as_bytes(), message] {
+        hash.update((part.len() as u64).to_le_bytes());
+        hash.update(part);
+    }
+}
+
+#[test]
+fn test_challenges() {
+    use sha2::Sha512;
+
+    use crate::{
+        ristretto::{RistrettoPoint, TestRistrettoPoint},
+        Named,
+    };
+
+    let x = TestScalar::from(5u8).named("x");
+    let big_x = TestRistrettoPoint::mul_base(&x).named("X");
+    let transcript = || {
+        let mut t = FiatShamir::<Sha512>::new("schnorr");
+        t.absorb("X", &big_x);
+        t
+    };
+
+    let mut t = transcript();
+    let c0 = t.challenge();
+    t.absorb("x", &x);
+    let c1 = t.challenge();
+
+    assert_eq!(format!("{c0:?}"), "Scalar(⟨schnorr#0⟩)");
+    assert_eq!(format!("{c1:?}"), "Scalar(⟨schnorr#1⟩)");
+    assert_ne!(c0.tree, c1.tree);
+    assert_ne!(c0, c1);
+    assert!(transcript().challenge().strict_eq(&c0));
+    assert_ne!(FiatShamir::<Sha512>::new("other").challenge(), c0);
+}
@@ -1,18 +1,118 @@
 //#![warn(missing_docs)]
 #![deny(clippy::correctness)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
 //! Debugging utilities for the curve25519-dalek crate
 
+extern crate alloc;
+
+use alloc::string::String;
+#[cfg(feature = "std")]
+use core::cell::Cell;
+
 #[macro_use]
 mod macros;
 
+pub mod counter;
+pub mod edwards;
+pub mod expr;
+#[cfg(feature = "digest")]
+pub mod fiat_shamir;
+pub mod montgomery;
+pub mod recorder;
 pub mod ristretto;
+#[cfg(feature = "rand_core")]
+pub mod rng;
 pub mod scalar;
-
-mod expr;
+pub mod snapshot;
+pub mod tracked;
+#[cfg(feature = "transcript")]
+pub mod transcript;
 
 pub trait Named {
     fn named<S>(self, name: S) -> Self
     where
         String: From<S>;
 }
+
+#[cfg(feature = "std")]
+thread_local! {
+    static CT_STRICT: Cell<bool> = const { Cell::new(false) };
+    static MAX_TREE_DEPTH: Cell<Option<usize>> = const { Cell::new(None) };
+    static NEXT_AUTO_NAME: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Makes variable-time operations on tracked values panic on the current thread
+#[cfg(feature = "std")]
+pub fn set_ct_strict(strict: bool) {
+    CT_STRICT.with(|s| s.set(strict));
+}
+
+pub(crate) fn check_vartime(_op: &str) {
+    #[cfg(feature = "std")]
+    if CT_STRICT.with(Cell::get) {
+        panic!("variable-time operation `{_op}` used in constant-time strict mode");
+    }
+}
+
+/// Makes the operators on the current thread panic when they build a tree deeper than `max`, or
+/// lifts the limit with `None`
+///
+/// This catches runaway expressions early, e.g. a loop that keeps growing a value it should
+/// have reset.
+#[cfg(feature = "std")]
+pub fn set_max_tree_depth(max: Option<usize>) {
+    MAX_TREE_DEPTH.with(|m| m.set(max));
+}
+
+pub(crate) fn check_depth(_tree: &expr::Tree) {
+    #[cfg(feature = "std")]
+    if let Some(max) = MAX_TREE_DEPTH.with(Cell::get) {
+        let depth = _tree.depth();
+        if depth > max {
+            panic!("expression of depth {depth} exceeds the maximum tree depth of {max}");
+        }
+    }
+}
+
+/// Makes the conversions into tracked values on the current thread name them `v0`, `v1`, …
+/// instead of leaving them unnamed, or stops naming them with `false`
+///
+/// The numbering restarts from `v0` every time the mode is turned on, so the names only depend
+/// on the order of the conversions on this thread.
+#[cfg(feature = "std")]
+pub fn set_auto_names(auto: bool) {
+    NEXT_AUTO_NAME.with(|n| n.set(auto.then_some(0)));
+}
+
+/// The next automatic name, if the mode is on
+pub(crate) fn auto_name() -> Option<String> {
+    #[cfg(feature = "std")]
+    return NEXT_AUTO_NAME.with(|n| {
+        let i = n.get()?;
+        n.set(Some(i + 1));
+        Some(alloc::format!("v{i}"))
+    });
+    #[cfg(not(feature = "std"))]
+    return None;
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    use core::fmt::Write;
+
+    bytes.iter().fold(String::new(), |mut s, b| {
+        write!(s, "{b:02x}").unwrap();
+        s
+    })
+}
+
+/// Parses JSON of any depth, since trees of long computations nest deeper than the default
+/// recursion limit of serde_json
+#[cfg(feature = "serde")]
+pub(crate) fn from_json<T: serde::de::DeserializeOwned>(json: &[u8]) -> serde_json::Result<T> {
+    let mut de = serde_json::Deserializer::from_slice(json);
+    de.disable_recursion_limit();
+    let value = T::deserialize(serde_stacker::Deserializer::new(&mut de))?;
+    de.end()?;
+    Ok(value)
+}
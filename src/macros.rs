@@ -94,6 +94,42 @@ macro_rules! define_mul_variants {
     };
 }
 
+macro_rules! define_div_variants {
+    (LHS = $lhs:ty, RHS = $rhs:ty, Output = $out:ty) => {
+        impl<'b> Div<&'b $rhs> for $lhs {
+            type Output = $out;
+            fn div(self, rhs: &'b $rhs) -> $out {
+                &self / rhs
+            }
+        }
+
+        impl<'a> Div<$rhs> for &'a $lhs {
+            type Output = $out;
+            fn div(self, rhs: $rhs) -> $out {
+                self / &rhs
+            }
+        }
+
+        impl Div<$rhs> for $lhs {
+            type Output = $out;
+            fn div(self, rhs: $rhs) -> $out {
+                &self / &rhs
+            }
+        }
+    };
+}
+
+macro_rules! define_neg_variants {
+    (Type = $t:ty) => {
+        impl Neg for $t {
+            type Output = $t;
+            fn neg(self) -> $t {
+                self.negate()
+            }
+        }
+    };
+}
+
 macro_rules! define_mul_assign_variants {
     (LHS = $lhs:ty, RHS = $rhs:ty) => {
         impl MulAssign<$rhs> for $lhs {
@@ -103,3 +139,14 @@ macro_rules! define_mul_assign_variants {
         }
     };
 }
+
+/// Asserts that two tracked values are equal and were computed by equivalent expressions
+///
+/// The expressions are compared after [`Tree::canonicalize`](crate::expr::Tree::canonicalize),
+/// so they may differ in the order and grouping of sums and products.
+#[macro_export]
+macro_rules! assert_equivalent {
+    ($left:expr, $right:expr $(,)?) => {
+        $crate::tracked::assert_equivalent(&$left, &$right)
+    };
+}
@@ -0,0 +1,49 @@
+//! Debugging utilities for [curve25519_dalek::montgomery]
+
+use alloc::boxed::Box;
+use core::fmt::{self, Debug};
+
+use curve25519_dalek::{montgomery::MontgomeryPoint, scalar::Scalar as DalekScalar};
+
+use crate::{
+    edwards::TestEdwardsPoint,
+    expr::Tree,
+    tracked::{Encoding, MulTree, Tracked},
+};
+
+pub type TestMontgomeryPoint = Tracked<MontgomeryPoint>;
+
+impl MulTree<MontgomeryPoint> for DalekScalar {
+    fn mul_tree(scalar: Tree, point: Tree) -> Tree {
+        Tree::scalar_mul_of(scalar, point)
+    }
+}
+
+impl Encoding for MontgomeryPoint {
+    fn encoding(&self) -> [u8; 32] {
+        self.to_bytes()
+    }
+}
+
+impl MulTree<DalekScalar> for MontgomeryPoint {
+    fn mul_tree(point: Tree, scalar: Tree) -> Tree {
+        Tree::scalar_mul_of(scalar, point)
+    }
+}
+
+impl Debug for TestMontgomeryPoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("MontgomeryPoint").field(&self.tree).finish()
+    }
+}
+
+impl TestMontgomeryPoint {
+    /// Converts the u-coordinate back to an Edwards point with the given sign, recording the
+    /// change of model
+    pub fn to_edwards(&self, sign: u8) -> Option<TestEdwardsPoint> {
+        Some(Tracked {
+            value: self.value.to_edwards(sign)?,
+            tree: Tree::Convert("edwards".into(), Box::new(self.tree.clone())),
+        })
+    }
+}
@@ -0,0 +1,110 @@
+//! Manual recording of computations written against plain dalek scalars
+
+use alloc::{string::String, vec::Vec};
+use core::ops::Index;
+
+use curve25519_dalek::scalar::Scalar as DalekScalar;
+
+use crate::{
+    expr::Tree,
+    scalar::{Scalar, TestScalar},
+    tracked::Tracked,
+};
+
+/// A value recorded by a [`Recorder`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Handle(usize);
+
+/// Mirrors a computation on [`DalekScalar`]s step by step, building the tree of each result
+///
+/// This is meant for code that can't be switched to [`TestScalar`]: each operation is replayed on
+/// the recorder next to the original one, and refers to earlier results by their handles.
+#[derive(Clone, Debug, Default)]
+pub struct Recorder {
+    values: Vec<TestScalar>,
+}
+
+impl Recorder {
+    /// A recorder with no values yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an input of the computation under a name
+    pub fn input<S>(&mut self, value: DalekScalar, name: S) -> Handle
+    where
+        String: From<S>,
+    {
+        self.push(Tracked::from_named(value, name))
+    }
+
+    /// Records `a + b`
+    pub fn add(&mut self, a: Handle, b: Handle) -> Handle {
+        self.push(&self[a] + &self[b])
+    }
+
+    /// Records `a - b`
+    pub fn sub(&mut self, a: Handle, b: Handle) -> Handle {
+        self.push(&self[a] - &self[b])
+    }
+
+    /// Records `a * b`
+    pub fn mul(&mut self, a: Handle, b: Handle) -> Handle {
+        self.push(&self[a] * &self[b])
+    }
+
+    /// Records `-a`
+    pub fn neg(&mut self, a: Handle) -> Handle {
+        self.push(-&self[a])
+    }
+
+    /// Records the inverse of `a`, like [`Scalar::invert`]
+    pub fn invert(&mut self, a: Handle) -> Handle {
+        self.push(self[a].invert())
+    }
+
+    /// The value of a recorded result
+    pub fn value(&self, h: Handle) -> DalekScalar {
+        self[h].value
+    }
+
+    /// How a recorded result was computed
+    pub fn tree(&self, h: Handle) -> &Tree {
+        &self[h].tree
+    }
+
+    fn push(&mut self, x: TestScalar) -> Handle {
+        self.values.push(x);
+        Handle(self.values.len() - 1)
+    }
+}
+
+impl Index<Handle> for Recorder {
+    type Output = TestScalar;
+
+    /// The recorded result as a tracked scalar
+    fn index(&self, h: Handle) -> &TestScalar {
+        &self.values[h.0]
+    }
+}
+
+#[test]
+fn test_recorder() {
+    let (a, b, c) = (
+        DalekScalar::from(2u8),
+        DalekScalar::from(3u8),
+        DalekScalar::from(5u8),
+    );
+    let result = a * b + c;
+
+    let mut rec = Recorder::new();
+    let ha = rec.input(a, "a");
+    let hb = rec.input(b, "b");
+    let hc = rec.input(c, "c");
+    let ab = rec.mul(ha, hb);
+    let h = rec.add(ab, hc);
+
+    assert_eq!(rec.value(h), result);
+    assert_eq!(format!("{:?}", rec.tree(h)), "(a * b + c)");
+    assert_eq!(rec[h], result);
+}
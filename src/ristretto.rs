@@ -1,27 +1,37 @@
 //! Debugging utilities for [curve25519_dalek::ristretto]
 
-use std::{
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use core::cell::Cell;
+use core::{
     borrow::Borrow,
-    fmt::{self, Debug},
+    error::Error,
+    fmt::{self, Debug, Display},
     iter::Sum,
     ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
 use curve25519_dalek::{
-    ristretto::{CompressedRistretto, RistrettoPoint as DalekRistrettoPoint},
-    scalar::Scalar as DalekScalar,
-    traits::Identity,
+    constants::RISTRETTO_BASEPOINT_POINT,
+    ristretto::{
+        CompressedRistretto, RistrettoBasepointTable, RistrettoPoint as DalekRistrettoPoint,
+    },
+    scalar::{clamp_integer, Scalar as DalekScalar},
+    traits::{Identity, MultiscalarMul, VartimeMultiscalarMul},
 };
 
 #[cfg(feature = "digest")]
 use digest::{typenum::U64, Digest};
 #[cfg(feature = "rand_core")]
 use rand_core::CryptoRngCore;
-use subtle::{Choice, ConstantTimeEq};
 
 use crate::{
-    expr::Tree,
+    check_vartime,
+    counter::Op,
+    expr::{elided_hex, Tree},
+    hex,
     scalar::{Scalar, TestScalar},
+    tracked::{balanced, record_node, record_op, transcript_active, Encoding, MulTree, Tracked},
     Named,
 };
 
@@ -49,8 +59,52 @@ pub trait RistrettoPoint: Sized + Named {
 
     fn mul_base(scalar: &Self::Scalar) -> Self;
 
+    /// Computes `s₀·P₀ + s₁·P₁ + …`
+    ///
+    /// Tracked points record the terms in the order of the inputs, whatever order dalek
+    /// accumulates them in internally. Only the value comes from dalek.
+    fn multiscalar_mul<I, J>(scalars: I, points: J) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Self::Scalar>,
+        J: IntoIterator,
+        J::Item: Borrow<Self>;
+
+    fn vartime_multiscalar_mul<I, J>(scalars: I, points: J) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Self::Scalar>,
+        J: IntoIterator,
+        J::Item: Borrow<Self>;
+    fn optional_multiscalar_mul<I, J>(scalars: I, points: J) -> Option<Self>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Self::Scalar>,
+        J: IntoIterator<Item = Option<Self>>;
+
     #[allow(non_snake_case)]
     fn vartime_double_scalar_mul_basepoint(a: &Self::Scalar, A: &Self, b: &Self::Scalar) -> Self;
+
+    /// The expression that computed this point, if it is tracked
+    ///
+    /// Untracked implementations can rely on the default, which returns `None`.
+    fn tree(&self) -> Option<&Tree> {
+        None
+    }
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static SKIP_ZERO_TERMS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Makes multiscalar multiplications on the current thread leave the terms with a zero scalar
+/// out of their trees
+///
+/// The computed values are unaffected.
+#[cfg(feature = "std")]
+pub fn set_skip_zero_terms(skip: bool) {
+    SKIP_ZERO_TERMS.with(|s| s.set(skip));
 }
 
 impl Named for DalekRistrettoPoint {
@@ -106,43 +160,325 @@ impl RistrettoPoint for DalekRistrettoPoint {
         Self::mul_base(scalar)
     }
 
+    fn multiscalar_mul<I, J>(scalars: I, points: J) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Self::Scalar>,
+        J: IntoIterator,
+        J::Item: Borrow<Self>,
+    {
+        <Self as MultiscalarMul>::multiscalar_mul(scalars, points)
+    }
+
+    fn vartime_multiscalar_mul<I, J>(scalars: I, points: J) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Self::Scalar>,
+        J: IntoIterator,
+        J::Item: Borrow<Self>,
+    {
+        <Self as VartimeMultiscalarMul>::vartime_multiscalar_mul(scalars, points)
+    }
+
+    fn optional_multiscalar_mul<I, J>(scalars: I, points: J) -> Option<Self>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Self::Scalar>,
+        J: IntoIterator<Item = Option<Self>>,
+    {
+        <Self as VartimeMultiscalarMul>::optional_multiscalar_mul(scalars, points)
+    }
+
     #[allow(non_snake_case)]
     fn vartime_double_scalar_mul_basepoint(a: &Self::Scalar, A: &Self, b: &Self::Scalar) -> Self {
         Self::vartime_double_scalar_mul_basepoint(a, A, b)
     }
 }
 
-#[derive(Clone)]
-pub struct TestRistrettoPoint {
-    value: DalekRistrettoPoint,
-    tree: Tree,
+pub type TestRistrettoPoint = Tracked<DalekRistrettoPoint>;
+
+impl MulTree<DalekRistrettoPoint> for DalekScalar {
+    fn mul_tree(scalar: Tree, point: Tree) -> Tree {
+        Tree::scalar_mul_of(scalar, point)
+    }
 }
 
-impl PartialEq for TestRistrettoPoint {
-    fn eq(&self, other: &Self) -> bool {
-        self.value == other.value
+impl Encoding for DalekRistrettoPoint {
+    fn encoding(&self) -> [u8; 32] {
+        self.compress().to_bytes()
     }
 }
 
-impl Eq for TestRistrettoPoint {}
+impl MulTree<DalekScalar> for DalekRistrettoPoint {
+    fn mul_tree(point: Tree, scalar: Tree) -> Tree {
+        Tree::scalar_mul_of(scalar, point)
+    }
+}
+
+/// The tree of the weighted sum `s₀·P₀ + s₁·P₁ + …`, in the order of the inputs
+fn multiscalar_tree(
+    scalars: &[impl Borrow<TestScalar>],
+    points: &[impl Borrow<TestRistrettoPoint>],
+) -> Tree {
+    #[cfg(feature = "std")]
+    let skip_zero_terms = SKIP_ZERO_TERMS.with(Cell::get);
+    #[cfg(not(feature = "std"))]
+    let skip_zero_terms = false;
+    // the partial values are only needed by the transcript
+    let logged = transcript_active();
+    let mut sum: Option<(Tree, Option<DalekRistrettoPoint>)> = None;
+    let terms = Iterator::zip(scalars.iter(), points.iter())
+        .map(|(s, p)| (s.borrow(), p.borrow()))
+        .filter(|(s, _)| !(skip_zero_terms && s.value == DalekScalar::ZERO));
+    for (s, p) in terms {
+        let term = Tree::scalar_mul_of(s.tree.clone(), p.tree.clone());
+        let value = logged.then(|| s.value * p.value);
+        record_node(Op::Mul, &[&s.tree, &p.tree], &term, || {
+            value.unwrap().encoding()
+        });
+        sum = Some(match sum {
+            None => (term, value),
+            Some((acc, acc_value)) => {
+                let operands = logged.then(|| [acc.clone(), term.clone()]);
+                let tree = Tree::sum_of(acc, term);
+                let value = acc_value.zip(value).map(|(a, b)| a + b);
+                let [l, r] = operands
+                    .as_ref()
+                    .map_or([&Tree::Unnamed; 2], |[l, r]| [l, r]);
+                record_node(Op::Add, &[l, r], &tree, || value.unwrap().encoding());
+                (tree, value)
+            }
+        });
+    }
+    sum.map_or(Tree::Identity, |(tree, _)| tree)
+}
+
+/// A variable-time multiscalar multiplication, checked against the strict mode under the name of
+/// the public function that was called
+fn vartime_multiscalar(
+    function: &str,
+    scalars: &[impl Borrow<TestScalar>],
+    points: &[impl Borrow<TestRistrettoPoint>],
+) -> TestRistrettoPoint {
+    check_vartime(function);
+    let value = <DalekRistrettoPoint as VartimeMultiscalarMul>::vartime_multiscalar_mul(
+        scalars.iter().map(|s| s.borrow().value),
+        points.iter().map(|p| p.borrow().value),
+    );
+    Tracked {
+        value,
+        tree: Tree::Vartime(Box::new(multiscalar_tree(scalars, points))),
+    }
+}
 
 impl Debug for TestRistrettoPoint {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.debug_tuple("Scalar").field(&self.tree).finish()
+        f.debug_tuple("RistrettoPoint").field(&self.tree).finish()
+    }
+}
+
+/// A compressed point that remembers the expression of the point it came from
+///
+/// Like other tracked values, compressed points compare equal when their bytes are equal, whatever
+/// their trees.
+pub type TestCompressedRistretto = Tracked<CompressedRistretto>;
+
+impl Debug for TestCompressedRistretto {
+    /// Shows the tree followed by the encoding in hexadecimal, elided like the bytes in trees if
+    /// set by [`set_hex_elision`](crate::expr::set_hex_elision)
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("CompressedRistretto")
+            .field(&self.tree)
+            .field(&format_args!("{}", elided_hex(self.value.as_bytes())))
+            .finish()
     }
 }
 
-impl Named for TestRistrettoPoint {
-    fn named<S>(self, name: S) -> Self
+impl TestRistrettoPoint {
+    /// Like [`RistrettoPoint::from_hash`], noting the role of the hash in the transcript on its
+    /// tree
+    #[cfg(feature = "digest")]
+    pub fn from_hash_labeled<D, S>(hash: D, label: S) -> Self
+    where
+        D: Digest<OutputSize = U64> + Default,
+        String: From<S>,
+    {
+        Self {
+            value: DalekRistrettoPoint::from_hash(hash),
+            tree: Tree::Annotated(label.into(), Box::new(Tree::hash::<D>(None))),
+        }
+    }
+
+    /// Compresses each point, keeping its tree unlike [`RistrettoPoint::compress`]
+    pub fn compress_batch(points: &[Self]) -> Vec<TestCompressedRistretto> {
+        points
+            .iter()
+            .map(|p| Tracked {
+                value: p.value.compress(),
+                tree: p.tree.clone(),
+            })
+            .collect()
+    }
+
+    /// Compares the points by checking whether their difference is the identity
+    ///
+    /// The difference is returned along with the result, so a failed check can show the residual
+    /// expression that should have vanished.
+    pub fn equals_via_identity(&self, other: &Self) -> (bool, Self) {
+        let diff = self - other;
+        (diff.value == DalekRistrettoPoint::identity(), diff)
+    }
+
+    /// Whether both points have the same canonical encoding, i.e. are the same Ristretto point
+    /// whatever the representatives of their cosets
+    pub fn same_encoding(&self, other: &Self) -> bool {
+        self.value.compress() == other.value.compress()
+    }
+
+    /// The encodings of both points along with their expressions, to tell apart a point
+    /// computed wrong from a point computed differently
+    pub fn encoding_report(&self, other: &Self) -> EncodingReport {
+        EncodingReport {
+            lhs: self.tree.clone(),
+            lhs_bytes: self.value.compress().to_bytes(),
+            rhs: other.tree.clone(),
+            rhs_bytes: other.value.compress().to_bytes(),
+        }
+    }
+
+    /// The Ristretto basepoint, labeled `B`
+    pub fn basepoint() -> Self {
+        Self {
+            value: RISTRETTO_BASEPOINT_POINT,
+            tree: Tree::Basepoint,
+        }
+    }
+
+    /// The identity, labeled with a name instead of `O`
+    pub fn identity_named<S>(name: S) -> Self
     where
         String: From<S>,
     {
-        TestRistrettoPoint {
-            tree: Tree::Name(name.into()),
-            ..self
+        Self::identity().named(name)
+    }
+
+    /// Multiplies the basepoint by the integer `bytes` clamped as in X25519 key generation
+    pub fn mul_base_clamped(bytes: [u8; 32]) -> Self {
+        // the basepoint has order l, so reducing the clamped integer doesn't change the product
+        let scalar = DalekScalar::from_bytes_mod_order(clamp_integer(bytes));
+        let clamped = Tree::Clamp(Box::new(Tree::FromBytes(bytes)));
+        let product = Self {
+            value: DalekRistrettoPoint::mul_base(&scalar),
+            tree: Tree::scalar_mul_of(clamped.clone(), Tree::Basepoint),
+        };
+        record_op(Op::Mul, &[&clamped, &Tree::Basepoint], &product);
+        product
+    }
+
+    /// Decompresses a point from the 32 bytes of its compressed encoding
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, FromSliceError> {
+        let compressed = CompressedRistretto::from_slice(bytes)
+            .map_err(|_| FromSliceError::Length(bytes.len()))?;
+        Ok(Self {
+            value: compressed
+                .decompress()
+                .ok_or(FromSliceError::Decompression)?,
+            tree: Tree::FromBytes(compressed.to_bytes()),
+        })
+    }
+}
+
+/// The canonical encodings of two points and the expressions that computed them
+///
+/// Displays whether the encodings match, with both expressions when they differ.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncodingReport {
+    /// The expression of the first point
+    pub lhs: Tree,
+    /// The canonical encoding of the first point
+    pub lhs_bytes: [u8; 32],
+    /// The expression of the second point
+    pub rhs: Tree,
+    /// The canonical encoding of the second point
+    pub rhs_bytes: [u8; 32],
+}
+
+impl EncodingReport {
+    /// Whether both points encode to the same bytes, i.e. are the same point
+    pub fn same_encoding(&self) -> bool {
+        self.lhs_bytes == self.rhs_bytes
+    }
+}
+
+impl Display for EncodingReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (lhs, rhs) = (hex(&self.lhs_bytes), hex(&self.rhs_bytes));
+        match (self.same_encoding(), self.lhs == self.rhs) {
+            (true, true) => write!(f, "{:?} encodes to {lhs}", self.lhs),
+            (true, false) => write!(f, "{:?} and {:?} both encode to {lhs}", self.lhs, self.rhs),
+            (false, _) => write!(
+                f,
+                "{:?} encodes to {lhs} but {:?} encodes to {rhs}",
+                self.lhs, self.rhs
+            ),
         }
     }
 }
+
+/// Error returned when decoding a point from a slice fails
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FromSliceError {
+    /// The slice doesn't have 32 bytes
+    Length(usize),
+    /// The bytes aren't the encoding of a point
+    Decompression,
+}
+
+impl Display for FromSliceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FromSliceError::Length(len) => write!(f, "expected 32 bytes, got {len}"),
+            FromSliceError::Decompression => f.write_str("invalid compressed point"),
+        }
+    }
+}
+
+impl Error for FromSliceError {}
+
+/// Collects the terms of a multiscalar multiplication as scalar–point pairs, so the scalars and
+/// the points can't get out of step
+///
+/// The tree of the result records the terms in the order they were added.
+#[derive(Clone, Default)]
+pub struct MultiscalarBuilder {
+    scalars: Vec<TestScalar>,
+    points: Vec<TestRistrettoPoint>,
+}
+
+impl MultiscalarBuilder {
+    /// A builder with no terms, whose sum is the identity
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the term `scalar · point`
+    pub fn add_term(mut self, scalar: &TestScalar, point: &TestRistrettoPoint) -> Self {
+        self.scalars.push(scalar.clone());
+        self.points.push(point.clone());
+        self
+    }
+
+    /// The sum of the terms, computed with [`RistrettoPoint::multiscalar_mul`]
+    pub fn multiscalar_mul(&self) -> TestRistrettoPoint {
+        TestRistrettoPoint::multiscalar_mul(&self.scalars, &self.points)
+    }
+
+    /// The sum of the terms, computed with [`RistrettoPoint::vartime_multiscalar_mul`]
+    pub fn vartime_multiscalar_mul(&self) -> TestRistrettoPoint {
+        TestRistrettoPoint::vartime_multiscalar_mul(&self.scalars, &self.points)
+    }
+}
+
 impl RistrettoPoint for TestRistrettoPoint {
     type Scalar = TestScalar;
 
@@ -168,7 +504,10 @@ impl RistrettoPoint for TestRistrettoPoint {
     where
         D: Digest<OutputSize = U64> + Default,
     {
-        DalekRistrettoPoint::hash_from_bytes::<D>(input).into()
+        Self {
+            value: DalekRistrettoPoint::hash_from_bytes::<D>(input),
+            tree: Tree::hash::<D>(None),
+        }
     }
 
     #[cfg(feature = "digest")]
@@ -176,7 +515,10 @@ impl RistrettoPoint for TestRistrettoPoint {
     where
         D: Digest<OutputSize = U64> + Default,
     {
-        DalekRistrettoPoint::from_hash(hash).into()
+        Self {
+            value: DalekRistrettoPoint::from_hash(hash),
+            tree: Tree::hash::<D>(None),
+        }
     }
 
     fn from_uniform_bytes(bytes: &[u8; 64]) -> Self {
@@ -184,78 +526,184 @@ impl RistrettoPoint for TestRistrettoPoint {
     }
 
     fn mul_base(scalar: &Self::Scalar) -> Self {
-        DalekRistrettoPoint::mul_base(&scalar.value).into()
+        let product = Self {
+            value: DalekRistrettoPoint::mul_base(&scalar.value),
+            tree: Tree::scalar_mul_of(scalar.tree.clone(), Tree::Basepoint),
+        };
+        record_op(Op::Mul, &[&scalar.tree, &Tree::Basepoint], &product);
+        product
     }
 
-    #[allow(non_snake_case)]
-    fn vartime_double_scalar_mul_basepoint(a: &Self::Scalar, A: &Self, b: &Self::Scalar) -> Self {
-        DalekRistrettoPoint::vartime_double_scalar_mul_basepoint(&a.value, &A.value, &b.value)
-            .into()
-    }
-}
-
-impl From<DalekRistrettoPoint> for TestRistrettoPoint {
-    fn from(value: DalekRistrettoPoint) -> Self {
+    fn multiscalar_mul<I, J>(scalars: I, points: J) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Self::Scalar>,
+        J: IntoIterator,
+        J::Item: Borrow<Self>,
+    {
+        let scalars: Vec<_> = scalars.into_iter().collect();
+        let points: Vec<_> = points.into_iter().collect();
+        let value = <DalekRistrettoPoint as MultiscalarMul>::multiscalar_mul(
+            scalars.iter().map(|s| s.borrow().value),
+            points.iter().map(|p| p.borrow().value),
+        );
         Self {
             value,
-            tree: Tree::Unnamed,
+            tree: multiscalar_tree(&scalars, &points),
         }
     }
-}
 
-impl<'a, 'b> Add<&'b TestRistrettoPoint> for &'a TestRistrettoPoint {
-    type Output = TestRistrettoPoint;
+    fn vartime_multiscalar_mul<I, J>(scalars: I, points: J) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Self::Scalar>,
+        J: IntoIterator,
+        J::Item: Borrow<Self>,
+    {
+        let scalars: Vec<_> = scalars.into_iter().collect();
+        let points: Vec<_> = points.into_iter().collect();
+        vartime_multiscalar("vartime_multiscalar_mul", &scalars, &points)
+    }
 
-    fn add(self, rhs: &'b TestRistrettoPoint) -> Self::Output {
-        Self::Output {
-            value: self.value + rhs.value,
-            tree: Tree::Add(Box::new(self.tree.clone()), Box::new(rhs.tree.clone())),
+    fn optional_multiscalar_mul<I, J>(scalars: I, points: J) -> Option<Self>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Self::Scalar>,
+        J: IntoIterator<Item = Option<Self>>,
+    {
+        let scalars: Vec<_> = scalars.into_iter().collect();
+        let points: Vec<_> = points.into_iter().collect::<Option<_>>()?;
+        Some(vartime_multiscalar(
+            "optional_multiscalar_mul",
+            &scalars,
+            &points,
+        ))
+    }
+
+    #[allow(non_snake_case)]
+    fn vartime_double_scalar_mul_basepoint(a: &Self::Scalar, A: &Self, b: &Self::Scalar) -> Self {
+        check_vartime("vartime_double_scalar_mul_basepoint");
+        let value =
+            DalekRistrettoPoint::vartime_double_scalar_mul_basepoint(&a.value, &A.value, &b.value);
+        // the terms are recorded like those of a multiscalar multiplication
+        let logged = transcript_active();
+        let a_term = Tree::scalar_mul_of(a.tree.clone(), A.tree.clone());
+        record_node(Op::Mul, &[&a.tree, &A.tree], &a_term, || {
+            (a.value * A.value).encoding()
+        });
+        let b_term = Tree::scalar_mul_of(b.tree.clone(), Tree::Basepoint);
+        record_node(Op::Mul, &[&b.tree, &Tree::Basepoint], &b_term, || {
+            DalekRistrettoPoint::mul_base(&b.value).encoding()
+        });
+        let operands = logged.then(|| [a_term.clone(), b_term.clone()]);
+        let sum = Tree::sum_of(a_term, b_term);
+        let [l, r] = operands
+            .as_ref()
+            .map_or([&Tree::Unnamed; 2], |[l, r]| [l, r]);
+        record_node(Op::Add, &[l, r], &sum, || value.encoding());
+        Self {
+            value,
+            tree: Tree::Vartime(Box::new(sum)),
         }
     }
+
+    fn tree(&self) -> Option<&Tree> {
+        Some(&self.tree)
+    }
 }
+
 define_add_variants!(
     LHS = TestRistrettoPoint,
     RHS = TestRistrettoPoint,
     Output = TestRistrettoPoint
 );
 
-impl<'b> AddAssign<&'b TestRistrettoPoint> for TestRistrettoPoint {
-    fn add_assign(&mut self, rhs: &'b TestRistrettoPoint) {
-        self.value += rhs.value;
-        self.tree = Tree::Add(Box::new(self.tree.clone()), Box::new(rhs.tree.clone()))
-    }
-}
 define_add_assign_variants!(LHS = TestRistrettoPoint, RHS = TestRistrettoPoint);
 
-impl<'a, 'b> Sub<&'b TestRistrettoPoint> for &'a TestRistrettoPoint {
-    type Output = TestRistrettoPoint;
-
-    fn sub(self, rhs: &'b TestRistrettoPoint) -> Self::Output {
-        Self::Output {
-            value: self.value - rhs.value,
-            tree: Tree::Sub(Box::new(self.tree.clone()), Box::new(rhs.tree.clone())),
-        }
-    }
-}
 define_sub_variants!(
     LHS = TestRistrettoPoint,
     RHS = TestRistrettoPoint,
     Output = TestRistrettoPoint
 );
 
-impl<'b> SubAssign<&'b TestRistrettoPoint> for TestRistrettoPoint {
-    fn sub_assign(&mut self, rhs: &'b TestRistrettoPoint) {
-        self.value += rhs.value;
-        self.tree = Tree::Add(Box::new(self.tree.clone()), Box::new(rhs.tree.clone()))
-    }
-}
 define_sub_assign_variants!(LHS = TestRistrettoPoint, RHS = TestRistrettoPoint);
 
+define_neg_variants!(Type = TestRistrettoPoint);
+
 // TODO: ConditionallySelectable
 
-impl ConstantTimeEq for TestRistrettoPoint {
-    fn ct_eq(&self, other: &Self) -> Choice {
-        self.value.ct_eq(&other.value)
+/// Precomputed multiples of a point, recorded like scalar multiplications of the point itself
+///
+/// With the basepoint, `&table * &z` records `z · B` just like
+/// [`mul_base`](RistrettoPoint::mul_base).
+pub struct TestRistrettoBasepointTable {
+    table: RistrettoBasepointTable,
+    tree: Tree,
+}
+
+impl TestRistrettoBasepointTable {
+    /// Precomputes the multiples of `basepoint`, which the products will be recorded with
+    pub fn create(basepoint: &TestRistrettoPoint) -> Self {
+        Self {
+            table: RistrettoBasepointTable::create(&basepoint.value),
+            tree: basepoint.tree.clone(),
+        }
+    }
+
+    /// The point the table was created from, with its tree
+    pub fn basepoint(&self) -> TestRistrettoPoint {
+        Tracked {
+            value: self.table.basepoint(),
+            tree: self.tree.clone(),
+        }
+    }
+
+    /// Multiplies the point of the table by `scalar`, recorded as `scalar · point`
+    ///
+    /// This is also what `&table * &scalar` and `&scalar * &table` do.
+    pub fn mul_base(&self, scalar: &TestScalar) -> TestRistrettoPoint {
+        let product = Tracked {
+            value: &self.table * &scalar.value,
+            tree: Tree::scalar_mul_of(scalar.tree.clone(), self.tree.clone()),
+        };
+        record_op(Op::Mul, &[&scalar.tree, &self.tree], &product);
+        product
+    }
+}
+
+impl Debug for TestRistrettoBasepointTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("RistrettoBasepointTable")
+            .field(&self.tree)
+            .finish()
+    }
+}
+
+impl<'b> Mul<&'b TestScalar> for &TestRistrettoBasepointTable {
+    type Output = TestRistrettoPoint;
+
+    fn mul(self, scalar: &'b TestScalar) -> TestRistrettoPoint {
+        self.mul_base(scalar)
+    }
+}
+
+impl<'a> Mul<&'a TestRistrettoBasepointTable> for &TestScalar {
+    type Output = TestRistrettoPoint;
+
+    fn mul(self, table: &'a TestRistrettoBasepointTable) -> TestRistrettoPoint {
+        table.mul_base(self)
+    }
+}
+
+impl PartialEq<DalekRistrettoPoint> for TestRistrettoPoint {
+    fn eq(&self, other: &DalekRistrettoPoint) -> bool {
+        self.value == *other
+    }
+}
+
+impl PartialEq<TestRistrettoPoint> for DalekRistrettoPoint {
+    fn eq(&self, other: &TestRistrettoPoint) -> bool {
+        *self == other.value
     }
 }
 
@@ -263,7 +711,7 @@ impl Default for TestRistrettoPoint {
     fn default() -> Self {
         Self {
             value: DalekRistrettoPoint::default(),
-            tree: Tree::One,
+            tree: Tree::Identity,
         }
     }
 }
@@ -272,82 +720,103 @@ impl Identity for TestRistrettoPoint {
     fn identity() -> Self {
         Self {
             value: DalekRistrettoPoint::identity(),
-            tree: Tree::One,
+            tree: Tree::Identity,
         }
     }
 }
 
-impl<'b> MulAssign<&'b TestScalar> for TestRistrettoPoint {
-    fn mul_assign(&mut self, rhs: &'b TestScalar) {
-        self.value *= rhs.value;
-        self.tree = Tree::Mul(Box::new(self.tree.clone()), Box::new(rhs.tree.clone()))
-    }
-}
 define_mul_assign_variants!(LHS = TestRistrettoPoint, RHS = TestScalar);
 
-impl<'a, 'b> Mul<&'b TestScalar> for &'a TestRistrettoPoint {
-    type Output = TestRistrettoPoint;
-
-    fn mul(self, rhs: &'b TestScalar) -> Self::Output {
-        Self::Output {
-            value: self.value * rhs.value,
-            tree: Tree::Mul(Box::new(self.tree.clone()), Box::new(rhs.tree.clone())),
-        }
-    }
-}
 define_mul_variants!(
     LHS = TestRistrettoPoint,
     RHS = TestScalar,
     Output = TestRistrettoPoint
 );
 
-impl<'a, 'b> Mul<&'b TestRistrettoPoint> for &'a TestScalar {
-    type Output = TestRistrettoPoint;
-
-    fn mul(self, rhs: &'b TestRistrettoPoint) -> Self::Output {
-        Self::Output {
-            value: self.value * rhs.value,
-            tree: Tree::Mul(Box::new(self.tree.clone()), Box::new(rhs.tree.clone())),
-        }
-    }
-}
 define_mul_variants!(
     LHS = TestScalar,
     RHS = TestRistrettoPoint,
     Output = TestRistrettoPoint
 );
 
-impl<'a> Neg for &'a TestRistrettoPoint {
-    type Output = TestRistrettoPoint;
-
-    fn neg(self) -> Self::Output {
-        Self::Output {
-            value: self.value.neg(),
-            tree: Tree::Neg(Box::new(self.tree.clone())),
-        }
-    }
-}
-impl Neg for TestRistrettoPoint {
-    type Output = TestRistrettoPoint;
-
-    fn neg(self) -> Self::Output {
-        Self::Output {
-            value: self.value.neg(),
-            tree: Tree::Neg(Box::new(self.tree)),
-        }
-    }
-}
-
 impl<T> Sum<T> for TestRistrettoPoint
 where
     T: Borrow<TestRistrettoPoint>,
 {
+    /// Adds all the items, recording a balanced tree of sums
+    ///
+    /// The sum of no items is recorded as the identity.
     fn sum<I: Iterator<Item = T>>(iter: I) -> Self {
-        DalekRistrettoPoint::sum(iter.map(|x| x.borrow().value)).into() // TODO trees are lost
+        let items = iter.map(|x| x.borrow().clone()).collect();
+        balanced(items, Op::Add, |a, b| a + b, Tree::Add).unwrap_or(Self {
+            value: DalekRistrettoPoint::identity(),
+            tree: Tree::Identity,
+        })
+    }
+}
+
+/// Adds up the points until the first error, like summing into a `Result`
+///
+/// The tree of the sum is built from the items as for [`Sum`].
+pub fn try_sum<I, E>(iter: I) -> Result<TestRistrettoPoint, E>
+where
+    I: IntoIterator<Item = Result<TestRistrettoPoint, E>>,
+{
+    iter.into_iter().sum()
+}
+
+/// `p + q`, as a function
+pub fn add(p: &TestRistrettoPoint, q: &TestRistrettoPoint) -> TestRistrettoPoint {
+    p + q
+}
+
+/// `p - q`, as a function
+pub fn sub(p: &TestRistrettoPoint, q: &TestRistrettoPoint) -> TestRistrettoPoint {
+    p - q
+}
+
+/// `-p`, as a function
+pub fn neg(p: &TestRistrettoPoint) -> TestRistrettoPoint {
+    -p
+}
+
+/// `s * p`, as a function, recorded as `s · p`
+pub fn scalar_mul(s: &TestScalar, p: &TestRistrettoPoint) -> TestRistrettoPoint {
+    s * p
+}
+
+/// Renders the check `lhs == rhs` as an equation, e.g. `s · B = R + c · X`
+///
+/// Both sides are rendered with [`Tree::render_minimal`], so they only keep the parentheses
+/// they need.
+pub fn render_equation(lhs: &TestRistrettoPoint, rhs: &TestRistrettoPoint) -> String {
+    format!(
+        "{} = {}",
+        lhs.tree.render_minimal(),
+        rhs.tree.render_minimal()
+    )
+}
+
+/// Asserts that two verification equations compute the same point with the same expression
+///
+/// Both expressions are [canonicalized](Tree::canonicalize) first, so that e.g. an optimized
+/// verifier may add up its terms in another order than the reference one. On mismatch, the
+/// panic message shows both canonical expressions as trees along with the first difference.
+#[track_caller]
+pub fn assert_same_equation(lhs: &TestRistrettoPoint, rhs: &TestRistrettoPoint) {
+    let (l, r) = (lhs.tree.canonicalize(), rhs.tree.canonicalize());
+    if let Some((ld, rd)) = l.first_difference(&r) {
+        panic!(
+            "assertion `lhs ≡ rhs` failed: equations differ\n first difference: {ld:?} vs {rd:?}\n  lhs:\n{}\n  rhs:\n{}",
+            l.to_art(),
+            r.to_art()
+        );
+    }
+    if lhs.value != rhs.value {
+        panic!("assertion `lhs ≡ rhs` failed: values differ\n equation: {l:?}");
     }
 }
 
-// impl VartimeMultiscalarMul for RistrettoPoint
 // impl Zeroize for RistrettoPoint
 // Available on
 // crate feature zeroize
@@ -355,6 +824,7 @@ where
 // impl Copy for RistrettoPoint
 
 #[test]
+#[cfg(feature = "rand_core")]
 fn test() {
     let rng = &mut rand::thread_rng();
     let x = TestRistrettoPoint::random(rng).named("x");
@@ -363,3 +833,538 @@ fn test() {
 
     assert_eq!(&x * &z, x + y * z);
 }
+
+#[test]
+fn test_sub_assign() {
+    let p = TestRistrettoPoint::mul_base(&TestScalar::from(5u8)).named("P");
+    let q = TestRistrettoPoint::mul_base(&TestScalar::from(7u8)).named("Q");
+
+    let mut r = p.clone();
+    r -= &q;
+    assert_eq!(r.value, p.value - q.value);
+    assert_eq!(format!("{r:?}"), format!("{:?}", &p - &q));
+}
+
+#[test]
+#[cfg(feature = "std")]
+#[should_panic(expected = "constant-time strict mode")]
+fn test_ct_strict_vartime() {
+    let a = TestScalar::from(2u8);
+    let b = TestScalar::from(3u8);
+    let p = TestRistrettoPoint::mul_base(&a);
+
+    crate::set_ct_strict(true);
+    TestRistrettoPoint::vartime_double_scalar_mul_basepoint(&a, &p, &b);
+}
+
+#[test]
+#[cfg(feature = "std")]
+#[should_panic(expected = "`vartime_multiscalar_mul` used in constant-time strict mode")]
+fn test_ct_strict_vartime_multiscalar_mul() {
+    let a = TestScalar::from(2u8);
+    let p = TestRistrettoPoint::mul_base(&a);
+
+    crate::set_ct_strict(true);
+    TestRistrettoPoint::vartime_multiscalar_mul([&a], [&p]);
+}
+
+#[test]
+fn test_alternate_debug() {
+    let x = TestScalar::from(2u8).named("x");
+    let p = TestRistrettoPoint::mul_base(&x).named("P");
+    let q = &p + &p * &x;
+
+    assert_eq!(format!("{q:?}"), "RistrettoPoint((P + x · P))");
+    assert_eq!(
+        format!("{:#?}", q),
+        "RistrettoPoint(\n    Add(\n        P,\n        ScalarMul(\n            x,\n            P,\n        ),\n    ),\n)"
+    );
+    assert!(format!("{:#?}", &x * &x).lines().count() > 1);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_multiscalar_skip_zero_terms() {
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(0u8).named("b");
+    let c = TestScalar::from(3u8).named("c");
+    let p = TestRistrettoPoint::mul_base(&a).named("P");
+    let q = TestRistrettoPoint::mul_base(&c).named("Q");
+    let r = TestRistrettoPoint::mul_base(&b).named("R");
+
+    let full = TestRistrettoPoint::multiscalar_mul([&a, &b, &c], [&p, &q, &r]);
+    set_skip_zero_terms(true);
+    let pruned = TestRistrettoPoint::multiscalar_mul([&a, &b, &c], [&p, &q, &r]);
+    set_skip_zero_terms(false);
+
+    assert_eq!(full, &a * &p + &c * &r);
+    assert_eq!(full, pruned);
+    assert_eq!(
+        format!("{full:?}"),
+        "RistrettoPoint(((a · P + b · Q) + c · R))"
+    );
+    assert_eq!(format!("{pruned:?}"), "RistrettoPoint((a · P + c · R))");
+}
+
+#[test]
+fn test_optional_multiscalar_mul() {
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&a).named("P");
+    let q = TestRistrettoPoint::mul_base(&b).named("Q");
+
+    let all =
+        TestRistrettoPoint::optional_multiscalar_mul([&a, &b], [Some(p.clone()), Some(q.clone())])
+            .unwrap();
+    assert_eq!(all, &a * &p + &b * &q);
+    assert_eq!(format!("{all:?}"), "RistrettoPoint(⟂((a · P + b · Q)))");
+
+    let missing = TestRistrettoPoint::optional_multiscalar_mul([&a, &b], [Some(p), None]);
+    assert!(missing.is_none());
+}
+
+#[test]
+fn test_generic_tree() {
+    fn render<P: RistrettoPoint>(p: &P) -> Option<String> {
+        p.tree().map(|t| format!("{t:?}"))
+    }
+    fn render_scalar<S: Scalar>(s: &S) -> Option<String> {
+        s.tree().map(|t| format!("{t:?}"))
+    }
+
+    let x = TestScalar::from(2u8).named("x");
+    let p = TestRistrettoPoint::mul_base(&x).named("P");
+
+    assert_eq!(render(&(&p + &p)), Some("(P + P)".to_owned()));
+    assert_eq!(render(&p.value), None);
+    assert_eq!(render_scalar(&(-&x)), Some("-x".to_owned()));
+    assert_eq!(render_scalar(&x.value), None);
+}
+
+#[test]
+fn test_scalar_mul_order() {
+    let z = TestScalar::from(2u8).named("z");
+    let p = TestRistrettoPoint::mul_base(&z).named("P");
+
+    let mut q = p.clone();
+    q *= &z;
+
+    assert_eq!(format!("{:?}", &z * &p), format!("{:?}", &p * &z));
+    assert_eq!(format!("{:?}", &p * &z), "RistrettoPoint(z · P)");
+    assert_eq!(format!("{q:?}"), "RistrettoPoint(z · P)");
+}
+
+#[test]
+fn test_multiscalar_input_order() {
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let c = TestScalar::from(5u8).named("c");
+    let p = TestRistrettoPoint::mul_base(&b).named("P");
+    let q = TestRistrettoPoint::mul_base(&c).named("Q");
+    let r = TestRistrettoPoint::mul_base(&a).named("R");
+
+    let x = TestRistrettoPoint::multiscalar_mul([&a, &b, &c], [&p, &q, &r]);
+    let y = TestRistrettoPoint::multiscalar_mul([&c, &a, &b], [&r, &p, &q]);
+
+    assert_eq!(x, y);
+    assert_eq!(
+        format!("{x:?}"),
+        "RistrettoPoint(((a · P + b · Q) + c · R))"
+    );
+    assert_eq!(
+        format!("{y:?}"),
+        "RistrettoPoint(((c · R + a · P) + b · Q))"
+    );
+}
+
+#[test]
+fn test_from_slice() {
+    let x = TestScalar::from(7u8);
+    let p = TestRistrettoPoint::mul_base(&x);
+    let bytes = p.compress().to_bytes();
+
+    let q = TestRistrettoPoint::from_slice(&bytes).unwrap();
+    assert_eq!(p, q);
+    assert_eq!(q.tree, Tree::FromBytes(bytes));
+    assert_eq!(
+        TestRistrettoPoint::from_slice(&bytes[..31]),
+        Err(FromSliceError::Length(31))
+    );
+    assert_eq!(
+        TestRistrettoPoint::from_slice(&[0xff; 32]),
+        Err(FromSliceError::Decompression)
+    );
+}
+
+#[test]
+fn test_identity() {
+    let o = TestRistrettoPoint::identity();
+    let e = TestRistrettoPoint::identity_named("E");
+    let x = TestScalar::ONE.named("x");
+    let (scalars, points): ([TestScalar; 0], [TestRistrettoPoint; 0]) = ([], []);
+
+    assert_eq!(format!("{o:?}"), "RistrettoPoint(O)");
+    assert_eq!(
+        format!("{:?}", TestRistrettoPoint::default()),
+        "RistrettoPoint(O)"
+    );
+    assert_eq!(format!("{:?}", &o * &x), "RistrettoPoint(x · O)");
+    assert_eq!(format!("{e:?}"), "RistrettoPoint(E)");
+    assert_eq!(o, e);
+    assert_eq!(
+        format!("{:?}", TestRistrettoPoint::multiscalar_mul(scalars, points)),
+        "RistrettoPoint(O)"
+    );
+}
+
+#[test]
+fn test_multiscalar_builder() {
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let c = TestScalar::from(5u8).named("c");
+    let p = TestRistrettoPoint::mul_base(&b).named("P");
+    let q = TestRistrettoPoint::mul_base(&c).named("Q");
+    let r = TestRistrettoPoint::mul_base(&a).named("R");
+
+    let built = MultiscalarBuilder::new()
+        .add_term(&a, &p)
+        .add_term(&b, &q)
+        .add_term(&c, &r)
+        .multiscalar_mul();
+    let raw = TestRistrettoPoint::multiscalar_mul([&a, &b, &c], [&p, &q, &r]);
+
+    assert_eq!(built, raw);
+    assert_eq!(format!("{built:?}"), format!("{raw:?}"));
+}
+
+#[test]
+fn test_mul_base_clamped() {
+    use curve25519_dalek::edwards::EdwardsPoint;
+
+    let bytes = [0xa5; 32];
+    let p = TestRistrettoPoint::mul_base_clamped(bytes);
+    let clamped = DalekScalar::from_bytes_mod_order(clamp_integer(bytes));
+
+    assert_eq!(p.value, DalekRistrettoPoint::mul_base(&clamped));
+    assert_ne!(
+        p.value,
+        DalekRistrettoPoint::mul_base(&DalekScalar::from_bytes_mod_order(bytes))
+    );
+    assert_eq!(
+        EdwardsPoint::mul_base_clamped(bytes),
+        EdwardsPoint::mul_base(&clamped)
+    );
+    assert_eq!(
+        format!("{p:?}"),
+        format!("RistrettoPoint(clamp(bytes({})) · B)", "a5".repeat(32))
+    );
+    assert_eq!(
+        p.tree.as_linear_combination().unwrap()[0].0.try_evaluate(),
+        Some(clamped)
+    );
+}
+
+#[test]
+fn test_eq_dalek_point() {
+    let x = TestScalar::from(3u8).named("x");
+    let p = TestRistrettoPoint::mul_base(&x).named("P");
+    let raw = DalekRistrettoPoint::mul_base(&DalekScalar::from(3u8));
+
+    assert_eq!(p, raw);
+    assert_eq!(raw, p);
+    assert_ne!(p, DalekRistrettoPoint::identity());
+}
+
+#[test]
+fn test_try_sum() {
+    let x = TestScalar::from(2u8).named("x");
+    let p = TestRistrettoPoint::mul_base(&x).named("P");
+    let q = TestRistrettoPoint::mul_base(&x).named("Q");
+    let r = TestRistrettoPoint::mul_base(&x).named("R");
+
+    let sum = try_sum([Ok::<_, FromSliceError>(p.clone()), Ok(q.clone()), Ok(r)]).unwrap();
+    assert_eq!(format!("{sum:?}"), "RistrettoPoint(((P + Q) + R))");
+
+    let bad = [0xff; 32];
+    let items = [
+        Ok(p),
+        TestRistrettoPoint::from_slice(&bad),
+        TestRistrettoPoint::from_slice(&[0; 3]),
+        Ok(q),
+    ];
+    assert_eq!(try_sum(items), Err(FromSliceError::Decompression));
+}
+
+#[test]
+fn test_equals_via_identity() {
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&a).named("P");
+
+    let (equal, diff) = (&(&a + &b) * &p).equals_via_identity(&(&a * &p + &b * &p));
+    assert!(equal);
+    assert_eq!(diff, DalekRistrettoPoint::identity());
+
+    let (equal, diff) = (&(&a + &b) * &p).equals_via_identity(&(&a * &p + &a * &p));
+    assert!(!equal);
+    assert_eq!(
+        format!("{diff:?}"),
+        "RistrettoPoint(((a + b) · P - (a · P + a · P)))"
+    );
+}
+
+#[test]
+fn test_compress_batch() {
+    let a = TestScalar::from(2u8).named("a");
+    let p = TestRistrettoPoint::mul_base(&a).named("P");
+    let q = TestRistrettoPoint::mul_base(&a).named("Q");
+    let points = [p.clone(), &a * &q];
+
+    let compressed = TestRistrettoPoint::compress_batch(&points);
+    assert_eq!(compressed.len(), 2);
+    for (c, p) in compressed.iter().zip(&points) {
+        assert_eq!(c.value, p.value.compress());
+        assert_eq!(c.tree, p.tree);
+    }
+    assert_eq!(
+        format!("{:?}", compressed[1]),
+        format!(
+            "CompressedRistretto(a · Q, {})",
+            hex(compressed[1].value.as_bytes())
+        )
+    );
+}
+
+#[test]
+fn test_vartime_double_scalar_mul_basepoint() {
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&b).named("A");
+
+    let r = TestRistrettoPoint::vartime_double_scalar_mul_basepoint(&a, &p, &b);
+    assert_eq!(
+        r,
+        DalekRistrettoPoint::vartime_double_scalar_mul_basepoint(&a.value, &p.value, &b.value)
+    );
+    assert_eq!(format!("{r:?}"), "RistrettoPoint(⟂((a · A + b · B)))");
+    assert!(r.tree.contains_vartime());
+    assert!(!(&a * &p).tree.contains_vartime());
+}
+
+#[test]
+fn test_scalar_mul_node() {
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&a).named("P");
+
+    assert!(matches!((&b * &p).tree, Tree::ScalarMul(..)));
+    assert!(matches!((&p * &b).tree, Tree::ScalarMul(..)));
+    assert!(matches!((&a * &b).tree, Tree::Mul(..)));
+    assert_eq!(
+        format!("{:?}", &(&a * &b) * &p),
+        "RistrettoPoint(a * b · P)"
+    );
+    assert_eq!((&a * &p + &(&a * &b) * &p).tree.scalar_mul_count(), 2);
+}
+
+#[test]
+fn test_compressed_named() {
+    let a = TestScalar::from(2u8).named("a");
+    let pk = (&a * &TestRistrettoPoint::mul_base(&a).named("G")).named("pk");
+
+    let c = TestRistrettoPoint::compress_batch(core::slice::from_ref(&pk)).remove(0);
+    let named = c.clone().named("pk_bytes");
+    let bytes = pk.value.compress();
+
+    assert_eq!(named, c);
+    assert_eq!(named, TestCompressedRistretto::from(bytes));
+    assert_ne!(
+        named,
+        TestCompressedRistretto::from(CompressedRistretto([0; 32]))
+    );
+    assert_eq!(
+        format!("{named:?}"),
+        format!("CompressedRistretto(pk_bytes, {})", hex(bytes.as_bytes()))
+    );
+    assert_eq!(
+        format!("{:?}", c),
+        format!("CompressedRistretto(pk, {})", hex(bytes.as_bytes()))
+    );
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_compressed_hex_elision() {
+    use crate::expr::set_hex_elision;
+
+    let c = TestCompressedRistretto::from(CompressedRistretto([0xa5; 32])).named("c");
+
+    set_hex_elision(Some(4));
+    let elided = format!("{c:?}");
+    set_hex_elision(None);
+
+    assert_eq!(elided, "CompressedRistretto(c, a5a5…a5a5)");
+    assert_eq!(
+        format!("{c:?}"),
+        format!("CompressedRistretto(c, {})", "a5".repeat(32))
+    );
+}
+
+#[test]
+fn test_basepoint() {
+    let b = TestRistrettoPoint::basepoint();
+    let x = TestScalar::from(3u8).named("x");
+
+    assert_eq!(b, TestRistrettoPoint::mul_base(&TestScalar::ONE));
+    assert_eq!(format!("{b:?}"), "RistrettoPoint(B)");
+    assert_eq!(&x * &b, TestRistrettoPoint::mul_base(&x));
+    assert_eq!(format!("{:?}", &x * &b), "RistrettoPoint(x · B)");
+}
+
+#[test]
+fn test_basepoint_table() {
+    let z = TestScalar::from(5u8).named("z");
+    let table = TestRistrettoBasepointTable::create(&TestRistrettoPoint::basepoint());
+    let expected = TestRistrettoPoint::mul_base(&z);
+
+    assert!((&table * &z).strict_eq(&expected));
+    assert!((&z * &table).strict_eq(&expected));
+    assert_eq!(format!("{:?}", table.mul_base(&z)), format!("{expected:?}"));
+    assert_eq!(format!("{expected:?}"), "RistrettoPoint(z · B)");
+
+    let p = TestRistrettoPoint::mul_base(&z).named("P");
+    let table = TestRistrettoBasepointTable::create(&p);
+    assert!(table.basepoint().strict_eq(&p));
+    assert!((&table * &z).strict_eq(&(&z * &p)));
+}
+
+#[test]
+fn test_assert_same_equation() {
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&TestScalar::from(5u8)).named("P");
+    let q = TestRistrettoPoint::mul_base(&TestScalar::from(7u8)).named("Q");
+
+    assert_same_equation(&(&a * &p + &b * &q), &(&q * &b + &p * &a));
+}
+
+#[test]
+#[should_panic(expected = "equations differ")]
+fn test_assert_same_equation_swapped_term() {
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&TestScalar::from(5u8)).named("P");
+    let q = TestRistrettoPoint::mul_base(&TestScalar::from(7u8)).named("Q");
+
+    assert_same_equation(&(&a * &p + &b * &q), &(&a * &q + &b * &p));
+}
+
+#[test]
+fn test_functional_api() {
+    let s = TestScalar::from(2u8).named("s");
+    let p = TestRistrettoPoint::mul_base(&TestScalar::from(5u8)).named("P");
+    let q = TestRistrettoPoint::mul_base(&TestScalar::from(7u8)).named("Q");
+
+    assert!(add(&p, &q).strict_eq(&(&p + &q)));
+    assert!(sub(&p, &q).strict_eq(&(&p - &q)));
+    assert!(neg(&p).strict_eq(&-&p));
+    assert!(scalar_mul(&s, &p).strict_eq(&(&s * &p)));
+    assert!(sub(&scalar_mul(&s, &p), &q).strict_eq(&(&s * &p - &q)));
+}
+
+#[test]
+fn test_same_encoding() {
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&TestScalar::from(5u8)).named("P");
+
+    assert!(p.same_encoding(&p));
+    let report = p.encoding_report(&p);
+    assert!(report.same_encoding());
+    assert_eq!(report.lhs_bytes, p.compress().to_bytes());
+    assert_eq!(
+        report.to_string(),
+        format!("P encodes to {}", hex(&report.lhs_bytes))
+    );
+
+    let (x, y) = (&(&a + &b) * &p, &a * &p + &b * &p);
+    assert!(x.same_encoding(&y));
+    assert_eq!(
+        x.encoding_report(&y).to_string(),
+        format!(
+            "(a + b) · P and (a · P + b · P) both encode to {}",
+            hex(&x.compress().to_bytes())
+        )
+    );
+
+    let z = &a * &p;
+    assert!(!x.same_encoding(&z));
+    assert!(x
+        .encoding_report(&z)
+        .to_string()
+        .contains(" but a · P encodes to "));
+}
+
+#[test]
+fn test_render_equation() {
+    let s = TestScalar::from(2u8).named("s");
+    let c = TestScalar::from(3u8).named("c");
+    let r = TestRistrettoPoint::mul_base(&TestScalar::from(5u8)).named("R");
+    let x = TestRistrettoPoint::mul_base(&TestScalar::from(7u8)).named("X");
+
+    let lhs = TestRistrettoPoint::mul_base(&s);
+    let rhs = &r + &c * &x;
+    assert_eq!(render_equation(&lhs, &rhs), "s · B = R + c · X");
+    assert_eq!(
+        render_equation(&(&lhs - &r), &(&(&c + &s) * &x)),
+        "s · B - R = (c + s) · X"
+    );
+}
+
+#[test]
+#[cfg(feature = "rand_core")]
+fn test_differential_point_ops() {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    let rng = &mut StdRng::seed_from_u64(0x0d1f_f325);
+    let mut pool: Vec<(TestRistrettoPoint, DalekRistrettoPoint)> = (0..4)
+        .map(|i| {
+            let p = DalekRistrettoPoint::random(rng);
+            (TestRistrettoPoint::from(p).named(format!("P{i}")), p)
+        })
+        .collect();
+
+    for step in 0..200 {
+        let (i, j) = (rng.gen_range(0..pool.len()), rng.gen_range(0..pool.len()));
+        let s = DalekScalar::random(rng);
+        let ((p, dp), (q, dq)) = (pool[i].clone(), pool[j].clone());
+        let (op, tracked, dalek) = match rng.gen_range(0..7) {
+            0 => ("add", &p + &q, dp + dq),
+            1 => ("sub", &p - &q, dp - dq),
+            2 => ("neg", -&p, -dp),
+            3 => ("scalar mul", &TestScalar::from(s) * &p, s * dp),
+            4 => {
+                let mut p = p;
+                p += &q;
+                ("add assign", p, dp + dq)
+            }
+            5 => {
+                let mut p = p;
+                p -= &q;
+                ("sub assign", p, dp - dq)
+            }
+            _ => {
+                let mut p = p;
+                p *= &TestScalar::from(s);
+                ("mul assign", p, dp * s)
+            }
+        };
+        assert_eq!(
+            tracked.compress(),
+            dalek.compress(),
+            "step {step}: {op} diverged, recorded as {tracked:?}"
+        );
+        // name the result so that the trees don't keep growing
+        pool[i] = (tracked.named(format!("P{}", step + 4)), dalek);
+    }
+}
@@ -1,22 +1,31 @@
 //! Debugging utilities for [curve25519_dalek::scalar]
 
-use std::{
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+use core::{
     borrow::Borrow,
-    fmt::{self, Debug},
-    hash::{Hash, Hasher},
+    cmp::Ordering,
+    error::Error,
+    fmt::{self, Debug, Display},
     iter::{Product, Sum},
-    ops::{Add, AddAssign, Index, Mul, MulAssign, Neg, Sub, SubAssign},
+    ops::{Add, AddAssign, Div, Index, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
 use curve25519_dalek::scalar::Scalar as DalekScalar;
-use subtle::{Choice, ConstantTimeEq, CtOption};
+use subtle::CtOption;
 
 #[cfg(feature = "digest")]
 use digest::{typenum::U64, Digest};
 #[cfg(feature = "rand_core")]
 use rand_core::CryptoRngCore;
 
-use crate::{expr::Tree, Named};
+use crate::{
+    counter::Op,
+    expr::Tree,
+    hex,
+    snapshot::Snapshot,
+    tracked::{balanced, record_op, Encoding, MulTree, Tracked},
+    Named,
+};
 
 pub trait Scalar: Sized + Named {
     fn from_bytes_mod_order(bytes: [u8; 32]) -> Self;
@@ -42,6 +51,13 @@ pub trait Scalar: Sized + Named {
     fn as_bytes(&self) -> &[u8; 32];
     fn invert(&self) -> Self;
     fn batch_invert(inputs: &mut [Self]) -> Self;
+
+    /// The expression that computed this scalar, if it is tracked
+    ///
+    /// Untracked implementations can rely on the default, which returns `None`.
+    fn tree(&self) -> Option<&Tree> {
+        None
+    }
 }
 
 impl Named for DalekScalar {
@@ -75,6 +91,7 @@ impl Scalar for DalekScalar {
         Self::random(rng)
     }
 
+    #[cfg(feature = "digest")]
     fn hash_from_bytes<D>(input: &[u8]) -> Self
     where
         D: Digest<OutputSize = U64> + Default,
@@ -82,6 +99,7 @@ impl Scalar for DalekScalar {
         Self::hash_from_bytes::<D>(input)
     }
 
+    #[cfg(feature = "digest")]
     fn from_hash<D>(hash: D) -> Self
     where
         D: Digest<OutputSize = U64>,
@@ -106,37 +124,340 @@ impl Scalar for DalekScalar {
     }
 }
 
-#[derive(Clone)]
-pub struct TestScalar {
-    pub(crate) value: DalekScalar,
-    pub(crate) tree: Tree,
-}
+pub type TestScalar = Tracked<DalekScalar>;
+
+impl MulTree<DalekScalar> for DalekScalar {}
 
-impl PartialEq for TestScalar {
-    fn eq(&self, other: &Self) -> bool {
-        self.value == other.value
+impl Encoding for DalekScalar {
+    fn encoding(&self) -> [u8; 32] {
+        self.to_bytes()
     }
 }
 
-impl Eq for TestScalar {}
-
 impl Debug for TestScalar {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_tuple("Scalar").field(&self.tree).finish()
     }
 }
 
-impl Named for TestScalar {
-    fn named<S>(self, name: S) -> Self
+impl TestScalar {
+    /// Zero, labeled with a name instead of `0`
+    pub fn named_zero<S>(name: S) -> Self
+    where
+        String: From<S>,
+    {
+        Self::ZERO.named(name)
+    }
+
+    /// One, labeled with a name instead of `1`
+    pub fn named_one<S>(name: S) -> Self
+    where
+        String: From<S>,
+    {
+        Self::ONE.named(name)
+    }
+
+    /// The small constant `N`, labeled with its decimal value
+    ///
+    /// The constant is fixed at compile time, but the scalar itself can't be built in a `const`
+    /// context since its tree owns a `String`.
+    pub fn constant<const N: u64>() -> Self {
+        Self::from_named(DalekScalar::from(N), format!("{N}"))
+    }
+
+    /// Reduces the 32 bytes of `bytes` modulo the group order, like
+    /// [`Scalar::from_bytes_mod_order`]
+    pub fn try_from_slice(bytes: &[u8]) -> Result<Self, LengthError> {
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| LengthError { len: bytes.len() })?;
+        Ok(Self {
+            value: DalekScalar::from_bytes_mod_order(bytes),
+            tree: Tree::FromBytes(bytes),
+        })
+    }
+
+    /// Parses a decimal literal, reducing it modulo the group order if it overflows
+    ///
+    /// The literal is kept as the name of the resulting scalar.
+    pub fn from_dec_str(s: &str) -> Result<Self, ParseScalarError> {
+        Ok(Self {
+            value: parse_digits(s, 10)?,
+            tree: Tree::Name(s.into()),
+        })
+    }
+
+    /// Parses a big-endian hexadecimal literal, with or without a `0x` prefix, reducing it modulo
+    /// the group order if it overflows
+    ///
+    /// The literal is kept as the name of the resulting scalar, always with a `0x` prefix.
+    pub fn from_hex_str(s: &str) -> Result<Self, ParseScalarError> {
+        let digits = s.strip_prefix("0x").unwrap_or(s);
+        Ok(Self {
+            value: parse_digits(digits, 16)?,
+            tree: Tree::Name(format!("0x{digits}")),
+        })
+    }
+
+    /// Checks that the expression actually computes the value, as a lint against operators
+    /// recording the wrong operation
+    ///
+    /// This is best-effort: the expression can only be recomputed when all its leaves were named
+    /// with [`TestScalar::named_with_value`] (or are constants); otherwise the check passes.
+    pub fn check_consistency(&self) -> Result<(), Divergence> {
+        match self.tree.try_evaluate() {
+            Some(recomputed) if recomputed != self.value => Err(Divergence {
+                expression: self.tree.clone(),
+                recomputed: recomputed.to_bytes(),
+                stored: self.value.to_bytes(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// The hexadecimal values of both scalars, if they differ although their expressions are the
+    /// same up to [canonicalization](Tree::canonicalize)
+    ///
+    /// Such a value-only divergence means that one of the computations doesn't actually compute
+    /// its expression. Returns `None` when the values agree or the expressions differ.
+    pub fn diff_values(&self, other: &Self) -> Option<(String, String)> {
+        (self.value != other.value && self.tree.canonicalize() == other.tree.canonicalize())
+            .then(|| (hex(self.value.as_bytes()), hex(other.value.as_bytes())))
+    }
+
+    /// The index of the first byte, in the little-endian encoding, where the values differ, with
+    /// the bytes of both scalars there
+    pub fn byte_diff(&self, other: &Self) -> Option<(usize, u8, u8)> {
+        Iterator::zip(self.value.as_bytes().iter(), other.value.as_bytes())
+            .enumerate()
+            .find(|(_, (l, r))| l != r)
+            .map(|(i, (&l, &r))| (i, l, r))
+    }
+
+    /// Captures the current value and expression, to compare with another run
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            bytes: self.value.to_bytes(),
+            tree: self.tree.clone(),
+        }
+    }
+
+    /// Names this scalar like [`Named::named`], but keeps its value in the tree
+    pub fn named_with_value<S>(self, name: S) -> Self
     where
         String: From<S>,
     {
         TestScalar {
-            tree: Tree::Name(name.into()),
+            tree: Tree::Value(name.into(), self.value.to_bytes()),
             ..self
         }
     }
+
+    /// Reduces 64 bytes squeezed from a transcript modulo the group order, labeling the result as
+    /// the verifier challenge `name`
+    pub fn challenge<S>(name: S, bytes: &[u8; 64]) -> Self
+    where
+        String: From<S>,
+    {
+        Self {
+            value: DalekScalar::from_bytes_mod_order_wide(bytes),
+            tree: Tree::Challenge(name.into()),
+        }
+    }
+
+    /// Multiplies this scalar by itself, recorded as `x^2`
+    ///
+    /// Repeated squarings are collapsed into a single power by [`Tree::simplify`].
+    pub fn square(&self) -> Self {
+        let square = Self {
+            value: self.value * self.value,
+            tree: Tree::Pow(Box::new(self.tree.clone()), 2),
+        };
+        record_op(Op::Mul, &[&self.tree, &self.tree], &square);
+        square
+    }
+
+    /// Attaches a note to the expression of this scalar, e.g. to point out what it stands for
+    ///
+    /// Unlike [`Named::named`], the expression is kept under the note.
+    pub fn annotate<S>(self, note: S) -> Self
+    where
+        String: From<S>,
+    {
+        Self {
+            tree: Tree::Annotated(note.into(), Box::new(self.tree)),
+            ..self
+        }
+    }
+
+    /// Forgets how this scalar was computed, keeping its value under a fresh name
+    ///
+    /// This behaves like [`Named::named`], but marks the point where a long history is cut short to
+    /// keep the trees that build on it small.
+    pub fn checkpoint<S>(self, name: S) -> Self
+    where
+        String: From<S>,
+    {
+        self.named(name)
+    }
+
+    /// Hashes the domain separator followed by `input` to a scalar, recording both the digest and
+    /// the domain in the tree
+    #[cfg(feature = "digest")]
+    pub fn hash_from_bytes_in_domain<D>(domain: &str, input: &[u8]) -> Self
+    where
+        D: Digest<OutputSize = U64> + Default,
+    {
+        Self::from_hash_in_domain(
+            domain,
+            D::default().chain_update(domain).chain_update(input),
+        )
+    }
+
+    /// Like [`Scalar::from_hash`], labeling the tree with the domain separator already fed to
+    /// `hash`
+    #[cfg(feature = "digest")]
+    pub fn from_hash_in_domain<D>(domain: &str, hash: D) -> Self
+    where
+        D: Digest<OutputSize = U64>,
+    {
+        Self {
+            value: DalekScalar::from_hash(hash),
+            tree: Tree::hash::<D>(Some(domain)),
+        }
+    }
+
+    /// Like [`Scalar::from_hash`], noting the role of the hash in the transcript on its tree
+    #[cfg(feature = "digest")]
+    pub fn from_hash_labeled<D, S>(hash: D, label: S) -> Self
+    where
+        D: Digest<OutputSize = U64>,
+        String: From<S>,
+    {
+        Self {
+            value: DalekScalar::from_hash(hash),
+            tree: Tree::Annotated(label.into(), Box::new(Tree::hash::<D>(None))),
+        }
+    }
+
+    /// Like [`Scalar::batch_invert`], but fails instead of inverting a zero element
+    ///
+    /// The inputs are left untouched when an error is returned.
+    pub fn try_batch_invert(inputs: &mut [Self]) -> Result<Self, BatchInvertError> {
+        match inputs.iter().position(|s| s.value == DalekScalar::ZERO) {
+            Some(index) => Err(BatchInvertError {
+                index,
+                expression: inputs[index].tree.clone(),
+            }),
+            None => Ok(Self::batch_invert(inputs)),
+        }
+    }
+
+    /// The tree of the `i`-th byte of the canonical encoding, as indexed by `self[i]`
+    ///
+    /// # Panics
+    ///
+    /// If `i` is not less than 32, like indexing.
+    pub fn byte_tree(&self, i: usize) -> Tree {
+        assert!(i < 32, "byte index {i} out of range for a scalar");
+        Tree::Byte(Box::new(self.tree.clone()), i)
+    }
+
+    /// Renders the expression followed by the value of each of its named leaves
+    ///
+    /// Only leaves named with [`TestScalar::named_with_value`] have a known value, the others are
+    /// listed as `?`.
+    pub fn render_with_legend(&self) -> String {
+        let mut out = format!("{:?}\nwhere\n", self.tree);
+        for (name, value) in self.tree.legend() {
+            let value = match value {
+                Some(bytes) => hex(bytes),
+                None => "?".into(),
+            };
+            out.push_str(&format!("  {name} = {value}\n"));
+        }
+        out
+    }
+}
+
+/// An expression whose recomputed value doesn't match the value stored alongside it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub expression: Tree,
+    pub recomputed: [u8; 32],
+    pub stored: [u8; 32],
 }
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} evaluates to {} but the stored value is {}",
+            self.expression,
+            hex(&self.recomputed),
+            hex(&self.stored)
+        )
+    }
+}
+
+/// Error returned by [`TestScalar::try_batch_invert`] when one of the inputs is zero
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchInvertError {
+    /// The position of the first zero input
+    pub index: usize,
+    /// The expression of that input
+    pub expression: Tree,
+}
+
+impl Display for BatchInvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cannot batch invert: input {} ({:?}) is zero",
+            self.index, self.expression
+        )
+    }
+}
+
+impl Error for BatchInvertError {}
+
+/// Error returned by [`TestScalar::try_from_slice`] when the slice doesn't have 32 bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LengthError {
+    pub len: usize,
+}
+
+impl Display for LengthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected 32 bytes, got {}", self.len)
+    }
+}
+
+impl Error for LengthError {}
+
+/// Error returned when parsing a scalar literal fails
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseScalarError;
+
+impl Display for ParseScalarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid scalar literal")
+    }
+}
+
+impl Error for ParseScalarError {}
+
+fn parse_digits(digits: &str, radix: u32) -> Result<DalekScalar, ParseScalarError> {
+    if digits.is_empty() {
+        return Err(ParseScalarError);
+    }
+    let radix_scalar = DalekScalar::from(radix);
+    digits.chars().try_fold(DalekScalar::ZERO, |acc, c| {
+        let digit = c.to_digit(radix).ok_or(ParseScalarError)?;
+        Ok(acc * radix_scalar + DalekScalar::from(digit))
+    })
+}
+
 impl Scalar for TestScalar {
     fn from_bytes_mod_order(bytes: [u8; 32]) -> Self {
         DalekScalar::from_bytes_mod_order(bytes).into()
@@ -160,22 +481,31 @@ impl Scalar for TestScalar {
         tree: Tree::One,
     };
 
+    #[cfg(feature = "rand_core")]
     fn random<R: CryptoRngCore + ?Sized>(rng: &mut R) -> Self {
         DalekScalar::random(rng).into()
     }
 
+    #[cfg(feature = "digest")]
     fn hash_from_bytes<D>(input: &[u8]) -> Self
     where
         D: Digest<OutputSize = U64> + Default,
     {
-        DalekScalar::hash_from_bytes::<D>(input).into()
+        Self {
+            value: DalekScalar::hash_from_bytes::<D>(input),
+            tree: Tree::hash::<D>(None),
+        }
     }
 
+    #[cfg(feature = "digest")]
     fn from_hash<D>(hash: D) -> Self
     where
         D: Digest<OutputSize = U64>,
     {
-        DalekScalar::from_hash(hash).into()
+        Self {
+            value: DalekScalar::from_hash(hash),
+            tree: Tree::hash::<D>(None),
+        }
     }
 
     fn to_bytes(&self) -> [u8; 32] {
@@ -187,95 +517,64 @@ impl Scalar for TestScalar {
     }
 
     fn invert(&self) -> Self {
-        Self {
+        let inverse = Self {
             value: self.value.invert(),
             tree: Tree::Inv(Box::new(self.tree.clone())),
-        }
+        };
+        record_op(Op::Invert, &[&self.tree], &inverse);
+        inverse
     }
 
     fn batch_invert(inputs: &mut [Self]) -> Self {
         let mut values: Vec<_> = inputs.iter().map(|s| s.value).collect();
         let value = DalekScalar::batch_invert(values.as_mut());
-        Iterator::zip(inputs.iter_mut(), values.iter()).for_each(|(a, b)| a.value = *b);
-        value.into()
-    }
-}
-
-impl From<DalekScalar> for TestScalar {
-    fn from(value: DalekScalar) -> Self {
+        let product = inputs
+            .iter()
+            .map(|s| s.tree.clone())
+            .reduce(Tree::product_of)
+            .unwrap_or(Tree::One);
+        Iterator::zip(inputs.iter_mut(), values.iter()).for_each(|(a, b)| {
+            a.value = *b;
+            a.tree = Tree::Inv(Box::new(a.tree.clone()));
+        });
         Self {
             value,
-            tree: Tree::Unnamed,
+            tree: Tree::Inv(Box::new(product)),
         }
     }
-}
 
-impl<'b> MulAssign<&'b TestScalar> for TestScalar {
-    fn mul_assign(&mut self, rhs: &'b TestScalar) {
-        self.value *= rhs.value;
-        self.tree = Tree::Mul(Box::new(self.tree.clone()), Box::new(rhs.tree.clone()))
+    fn tree(&self) -> Option<&Tree> {
+        Some(&self.tree)
     }
 }
+
 define_mul_assign_variants!(LHS = TestScalar, RHS = TestScalar);
 
-impl<'a, 'b> Mul<&'b TestScalar> for &'a TestScalar {
-    type Output = TestScalar;
-    fn mul(self, rhs: &'b TestScalar) -> TestScalar {
-        Self::Output {
-            value: self.value * rhs.value,
-            tree: Tree::Mul(Box::new(self.tree.clone()), Box::new(rhs.tree.clone())),
-        }
-    }
-}
 define_mul_variants!(LHS = TestScalar, RHS = TestScalar, Output = TestScalar);
 
-impl<'b> AddAssign<&'b TestScalar> for TestScalar {
-    fn add_assign(&mut self, rhs: &'b TestScalar) {
-        self.value += rhs.value;
-        self.tree = Tree::Add(Box::new(self.tree.clone()), Box::new(rhs.tree.clone()))
-    }
-}
-define_add_assign_variants!(LHS = TestScalar, RHS = TestScalar);
-
-impl<'a, 'b> Add<&'b TestScalar> for &'a TestScalar {
+impl<'b> Div<&'b TestScalar> for &TestScalar {
     type Output = TestScalar;
 
-    fn add(self, rhs: &'b TestScalar) -> Self::Output {
-        Self::Output {
-            value: self.value + rhs.value,
-            tree: Tree::Add(Box::new(self.tree.clone()), Box::new(rhs.tree.clone())),
-        }
+    /// Multiplies by the inverse of `rhs`, recorded as `self * rhs⁻¹`
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, rhs: &'b TestScalar) -> TestScalar {
+        self * &rhs.invert()
     }
 }
+
+define_div_variants!(LHS = TestScalar, RHS = TestScalar, Output = TestScalar);
+
+define_add_assign_variants!(LHS = TestScalar, RHS = TestScalar);
+
 define_add_variants!(LHS = TestScalar, RHS = TestScalar, Output = TestScalar);
 
-impl<'b> SubAssign<&'b TestScalar> for TestScalar {
-    fn sub_assign(&mut self, rhs: &'b TestScalar) {
-        self.value -= rhs.value;
-        self.tree = Tree::Sub(Box::new(self.tree.clone()), Box::new(rhs.tree.clone()))
-    }
-}
 define_sub_assign_variants!(LHS = TestScalar, RHS = TestScalar);
 
-impl<'a, 'b> Sub<&'b TestScalar> for &'a TestScalar {
-    type Output = TestScalar;
-
-    fn sub(self, rhs: &'b TestScalar) -> Self::Output {
-        Self::Output {
-            value: self.value - rhs.value,
-            tree: Tree::Sub(Box::new(self.tree.clone()), Box::new(rhs.tree.clone())),
-        }
-    }
-}
 define_sub_variants!(LHS = TestScalar, RHS = TestScalar, Output = TestScalar);
 
-// TODO: ConditionallySelectable
+define_neg_variants!(Type = TestScalar);
 
-impl ConstantTimeEq for TestScalar {
-    fn ct_eq(&self, other: &Self) -> Choice {
-        self.value.ct_eq(&other.value)
-    }
-}
+// TODO: ConditionallySelectable
 
 impl Default for TestScalar {
     fn default() -> Self {
@@ -298,39 +597,48 @@ define_from!(u32);
 define_from!(u64);
 define_from!(u8);
 
-impl Hash for TestScalar {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.value.hash(state);
+/// Orders scalars by the integer value of their canonical bytes
+///
+/// This is only meant to give a stable order, e.g. for map keys or sorted reports. It is not a
+/// field ordering and the trees are not compared.
+impl PartialOrd for TestScalar {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
-impl Index<usize> for TestScalar {
-    type Output = u8;
+impl Ord for TestScalar {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let le = |s: &Self| s.value.to_bytes();
+        le(self).iter().rev().cmp(le(other).iter().rev())
+    }
+}
 
-    fn index(&self, index: usize) -> &Self::Output {
-        self.value.index(index)
+impl PartialEq<DalekScalar> for TestScalar {
+    fn eq(&self, other: &DalekScalar) -> bool {
+        self.value == *other
     }
 }
 
-impl<'a> Neg for &'a TestScalar {
-    type Output = TestScalar;
+impl PartialEq<TestScalar> for DalekScalar {
+    fn eq(&self, other: &TestScalar) -> bool {
+        *self == other.value
+    }
+}
 
-    fn neg(self) -> Self::Output {
-        Self::Output {
-            value: self.value.neg(),
-            tree: Tree::Neg(Box::new(self.tree.clone())),
-        }
+impl TryFrom<&[u8]> for TestScalar {
+    type Error = LengthError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, LengthError> {
+        Self::try_from_slice(bytes)
     }
 }
 
-impl Neg for TestScalar {
-    type Output = TestScalar;
+impl Index<usize> for TestScalar {
+    type Output = u8;
 
-    fn neg(self) -> Self::Output {
-        Self::Output {
-            value: self.value.neg(),
-            tree: Tree::Neg(Box::new(self.tree)),
-        }
+    fn index(&self, index: usize) -> &Self::Output {
+        self.value.index(index)
     }
 }
 
@@ -338,8 +646,16 @@ impl<T> Product<T> for TestScalar
 where
     T: Borrow<TestScalar>,
 {
+    /// Multiplies all the items, recording a balanced tree of products
+    ///
+    /// The product of no items is recorded as an empty [`Tree::MulMany`], which renders as `1`
+    /// but can be told apart from the constant.
     fn product<I: Iterator<Item = T>>(iter: I) -> Self {
-        DalekScalar::product(iter.map(|x| x.borrow().value)).into()
+        let items = iter.map(|x| x.borrow().clone()).collect();
+        balanced(items, Op::Mul, |a, b| a * b, Tree::Mul).unwrap_or(Self {
+            value: DalekScalar::ONE,
+            tree: Tree::MulMany(Vec::new()),
+        })
     }
 }
 
@@ -347,14 +663,395 @@ impl<T> Sum<T> for TestScalar
 where
     T: Borrow<TestScalar>,
 {
+    /// Adds all the items, recording a balanced tree of sums
+    ///
+    /// The sum of no items is recorded as an empty [`Tree::AddMany`], which renders as `0` but can
+    /// be told apart from the constant.
     fn sum<I: Iterator<Item = T>>(iter: I) -> Self {
-        DalekScalar::sum(iter.map(|x| x.borrow().value)).into() // TODO trees are lost
+        let items = iter.map(|x| x.borrow().clone()).collect();
+        balanced(items, Op::Add, |a, b| a + b, Tree::Add).unwrap_or(Self {
+            value: DalekScalar::ZERO,
+            tree: Tree::AddMany(Vec::new()),
+        })
     }
 }
 
+/// `a + b`, as a function
+pub fn add(a: &TestScalar, b: &TestScalar) -> TestScalar {
+    a + b
+}
+
+/// `a - b`, as a function
+pub fn sub(a: &TestScalar, b: &TestScalar) -> TestScalar {
+    a - b
+}
+
+/// `a * b`, as a function
+pub fn mul(a: &TestScalar, b: &TestScalar) -> TestScalar {
+    a * b
+}
+
+/// `-a`, as a function
+pub fn neg(a: &TestScalar) -> TestScalar {
+    -a
+}
+
+/// `a.invert()`, as a function
+pub fn invert(a: &TestScalar) -> TestScalar {
+    a.invert()
+}
+
 // impl Zeroize for TestScalar
 // Available on
 // crate feature zeroize
 //  only.
 
 // impl Copy for TestScalar
+
+#[test]
+fn test_render_with_legend() {
+    let a = TestScalar::from(2u8).named_with_value("a");
+    let b = TestScalar::from(3u8).named_with_value("b");
+    let legend = (&a * &a + &b).render_with_legend();
+
+    let zeros = "00".repeat(31);
+    assert_eq!(
+        legend,
+        format!("(a * a + b)\nwhere\n  a = 02{zeros}\n  b = 03{zeros}\n")
+    );
+}
+
+#[test]
+fn test_batch_invert_tree() {
+    let mut inputs = [
+        TestScalar::from(3u8).named("a"),
+        TestScalar::from(5u8).named("b"),
+        TestScalar::from(7u8).named("c"),
+    ];
+    let all = TestScalar::batch_invert(&mut inputs);
+
+    assert_eq!(all, TestScalar::from(105u8).invert());
+    assert_eq!(all.tree.variables(), ["a", "b", "c"]);
+    assert_eq!(format!("{:?}", inputs[1].tree), "b⁻¹");
+}
+
+#[test]
+fn test_product_depth() {
+    let scalars: Vec<_> = (1..=1000u16)
+        .map(|i| TestScalar::from(i).named(format!("x{i}")))
+        .collect();
+    let product: TestScalar = scalars.iter().product();
+
+    assert_eq!(
+        product.value,
+        scalars.iter().map(|x| x.value).product::<DalekScalar>()
+    );
+    assert_eq!(product.tree.variables().len(), 1000);
+    assert_eq!(product.tree.depth(), 11);
+}
+
+#[test]
+fn test_named_constants() {
+    let g = TestScalar::named_one("g");
+    let o = TestScalar::named_zero("o");
+
+    assert_eq!(g, TestScalar::ONE);
+    assert_eq!(o, TestScalar::ZERO);
+    assert_eq!(format!("{g:?}"), "Scalar(g)");
+    assert_eq!(format!("{:?}", &g + &o), "Scalar((g + o))");
+}
+
+#[test]
+fn test_parse_literals() {
+    let dec = TestScalar::from_dec_str("12345").unwrap();
+    let hex = TestScalar::from_hex_str("0x3039").unwrap();
+
+    assert_eq!(dec, hex);
+    assert_eq!(dec, TestScalar::from(12345u16));
+    assert_eq!(format!("{dec:?}"), "Scalar(12345)");
+    assert_eq!(format!("{hex:?}"), "Scalar(0x3039)");
+    assert_eq!(TestScalar::from_hex_str("3039").unwrap(), hex);
+
+    // one more than the group order
+    let order = TestScalar::from_dec_str(
+        "7237005577332262213973186563042994240857116359379907606001950938285454250990",
+    )
+    .unwrap();
+    assert_eq!(order, TestScalar::ONE);
+
+    assert_eq!(TestScalar::from_dec_str("12a"), Err(ParseScalarError));
+    assert_eq!(TestScalar::from_hex_str(""), Err(ParseScalarError));
+}
+
+#[test]
+fn test_check_consistency() {
+    let a = TestScalar::from(5u8).named_with_value("a");
+    let b = TestScalar::from(3u8).named_with_value("b");
+    let c = TestScalar::from(2u8).named("c");
+
+    assert_eq!((&a * &b - &a.invert()).check_consistency(), Ok(()));
+    assert_eq!((&a * &c).check_consistency(), Ok(()));
+
+    // an operator that computes a subtraction but records an addition
+    let corrupted = TestScalar {
+        value: a.value - b.value,
+        tree: Tree::Add(Box::new(a.tree.clone()), Box::new(b.tree.clone())),
+    };
+    let divergence = corrupted.check_consistency().unwrap_err();
+    assert_eq!(divergence.recomputed, TestScalar::from(8u8).to_bytes());
+    assert_eq!(divergence.stored, TestScalar::from(2u8).to_bytes());
+    assert!(divergence
+        .to_string()
+        .starts_with("(a + b) evaluates to 08"));
+}
+
+#[test]
+fn test_ord() {
+    let a = TestScalar::from(300u16).named("a");
+    let b = TestScalar::from(2u8).named("b");
+    let c = -TestScalar::from(1u8);
+
+    let mut scalars = vec![c.clone(), a.clone(), b.clone(), a.clone()];
+    scalars.sort();
+    assert_eq!(scalars, [b.clone(), a.clone(), a.clone(), c.clone()]);
+
+    for x in &scalars {
+        for y in &scalars {
+            assert_eq!(x.cmp(y), y.cmp(x).reverse());
+            assert_eq!(x.cmp(y).is_eq(), x == y);
+        }
+    }
+    assert_eq!(a.cmp(&TestScalar::from(300u16).named("d")), Ordering::Equal);
+}
+
+#[test]
+#[cfg(feature = "digest")]
+fn test_hash_tree() {
+    use sha2::{Digest, Sha512};
+
+    let x = TestScalar::hash_from_bytes::<Sha512>(b"input");
+    let y = TestScalar::hash_from_bytes_in_domain::<Sha512>("proof", b"input");
+    let z = TestScalar::from_hash(Sha512::new().chain_update("proof").chain_update(b"input"));
+
+    assert!(format!("{x:?}").contains("Sha512"));
+    assert_eq!(format!("{x:?}"), "Scalar(H<Sha512>)");
+    assert_eq!(format!("{y:?}"), "Scalar(H<Sha512>(\"proof\"))");
+    assert_eq!(x, DalekScalar::hash_from_bytes::<Sha512>(b"input"));
+    assert_eq!(y, z);
+}
+
+#[test]
+fn test_try_batch_invert() {
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let z = TestScalar::ZERO.named("z");
+
+    let mut inputs = [a.clone(), z, &b - &b];
+    let err = TestScalar::try_batch_invert(&mut inputs).unwrap_err();
+    assert_eq!(err.index, 1);
+    assert_eq!(err.to_string(), "cannot batch invert: input 1 (z) is zero");
+    assert_eq!(inputs[0], a);
+
+    let mut inputs = [a.clone(), b.clone()];
+    let product = TestScalar::try_batch_invert(&mut inputs).unwrap();
+    assert_eq!(product, (&a * &b).invert());
+    assert_eq!(inputs, [a.invert(), b.invert()]);
+}
+
+#[test]
+fn test_div_variants() {
+    let a = TestScalar::from(6u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let expected = TestScalar::from(2u8);
+
+    assert_eq!(&a / &b, expected);
+    assert_eq!(a.clone() / &b, expected);
+    assert_eq!(&a / b.clone(), expected);
+    assert_eq!(a.clone() / b.clone(), expected);
+    assert_eq!(format!("{:?}", a / b), "Scalar(a * b⁻¹)");
+}
+
+#[test]
+fn test_byte_tree() {
+    let a = TestScalar::from(258u16).named_with_value("a");
+    let b = TestScalar::from(3u8).named("b");
+
+    assert_eq!(format!("{:?}", a.byte_tree(0)), "a[0]");
+    assert_eq!(format!("{:?}", (&a * &b).byte_tree(31)), "(a * b)[31]");
+    assert_eq!(a.byte_tree(1).try_evaluate(), Some(DalekScalar::from(a[1])));
+}
+
+#[test]
+fn test_constant() {
+    let seven = TestScalar::constant::<7>();
+
+    assert_eq!(seven, TestScalar::from(7u64));
+    assert_eq!(format!("{seven:?}"), "Scalar(7)");
+    assert_eq!(
+        format!("{:?}", &seven * &TestScalar::constant::<{ u64::MAX }>()),
+        "Scalar(7 * 18446744073709551615)"
+    );
+}
+
+#[test]
+fn test_empty_sum_and_product() {
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let none: [TestScalar; 0] = [];
+
+    let sum: TestScalar = none.iter().sum();
+    let product: TestScalar = none.iter().product();
+    assert_eq!(sum, TestScalar::ZERO);
+    assert_eq!(product, TestScalar::ONE);
+    assert_eq!(format!("{sum:?}"), "Scalar(0)");
+    assert_eq!(format!("{product:?}"), "Scalar(1)");
+    assert_ne!(sum.tree, Tree::Zero);
+    assert_ne!(product.tree, Tree::One);
+
+    let sum: TestScalar = [&a, &b, &a].into_iter().sum();
+    assert_eq!(sum, TestScalar::from(7u8));
+    assert_eq!(format!("{sum:?}"), "Scalar(((a + b) + a))");
+}
+
+#[test]
+fn test_try_from_slice() {
+    let bytes = [0xff; 32];
+    let x = TestScalar::try_from_slice(&bytes).unwrap();
+
+    assert_eq!(x.value, DalekScalar::from_bytes_mod_order(bytes));
+    assert_eq!(x.tree, Tree::FromBytes(bytes));
+    assert_eq!(TestScalar::try_from(&bytes[..]), Ok(x));
+    assert_eq!(
+        TestScalar::try_from_slice(&bytes[..16]),
+        Err(LengthError { len: 16 })
+    );
+    assert_eq!(
+        TestScalar::try_from_slice(&[0; 33])
+            .unwrap_err()
+            .to_string(),
+        "expected 32 bytes, got 33"
+    );
+}
+
+#[test]
+fn test_checkpoint() {
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let x = (&a * &b + &a).invert() * &b;
+    let s = x.clone().checkpoint("s");
+
+    assert_eq!(s, x);
+    assert_eq!(format!("{s:?}"), "Scalar(s)");
+    assert_eq!(format!("{:?}", &s * &a), "Scalar(s * a)");
+}
+
+#[test]
+fn test_eq_dalek_scalar() {
+    let x = TestScalar::from(5u8).named("x");
+
+    assert_eq!(x, DalekScalar::from(5u8));
+    assert_eq!(DalekScalar::from(5u8), x);
+    assert_ne!(x, DalekScalar::ONE);
+}
+
+#[test]
+fn test_sum_and_product_by_reference() {
+    let xs = vec![
+        TestScalar::from(2u8).named("a"),
+        TestScalar::from(3u8).named("b"),
+        TestScalar::from(5u8).named("c"),
+    ];
+
+    let sum: TestScalar = xs.iter().sum();
+    let product: TestScalar = xs.iter().product();
+    assert_eq!(sum, DalekScalar::from(10u8));
+    assert_eq!(format!("{sum:?}"), "Scalar(((a + b) + c))");
+    assert_eq!(product, DalekScalar::from(30u8));
+    assert_eq!(format!("{product:?}"), "Scalar(a * b * c)");
+
+    let owned: TestScalar = xs.clone().into_iter().sum();
+    assert!(owned.strict_eq(&sum));
+}
+
+#[test]
+fn test_challenge() {
+    let bytes = [7; 64];
+    let c = TestScalar::challenge("c", &bytes);
+    let x = TestScalar::from(2u8).named("x");
+
+    assert_eq!(c, DalekScalar::from_bytes_mod_order_wide(&bytes));
+    assert_eq!(format!("{:?}", &c * &x), "Scalar(⟨c⟩ * x)");
+    assert_ne!(c.tree, Tree::Name("c".into()));
+}
+
+#[test]
+fn test_annotate() {
+    let a = TestScalar::from(2u8).named("a");
+    let r = TestScalar::from(3u8).named("r");
+    let c = (&a * &r).annotate("commitment");
+
+    assert_eq!(c, DalekScalar::from(6u8));
+    assert_eq!(format!("{c:?}"), "Scalar(/* commitment */ a * r)");
+    assert_eq!(
+        format!("{:?}", &c + &a),
+        "Scalar((/* commitment */ a * r + a))"
+    );
+}
+
+#[test]
+#[cfg(feature = "digest")]
+fn test_from_hash_labeled() {
+    use crate::ristretto::TestRistrettoPoint;
+    use curve25519_dalek::RistrettoPoint as DalekRistrettoPoint;
+    use sha2::{Digest, Sha512};
+
+    let transcript = Sha512::new().chain_update(b"transcript");
+    let c = TestScalar::from_hash_labeled(transcript.clone(), "challenge");
+    let p = TestRistrettoPoint::from_hash_labeled(transcript.clone(), "generator");
+
+    assert_eq!(c, DalekScalar::from_hash(transcript.clone()));
+    assert_eq!(format!("{c:?}"), "Scalar(/* challenge */ H<Sha512>)");
+    assert_eq!(p, DalekRistrettoPoint::from_hash(transcript));
+    assert_eq!(
+        format!("{p:?}"),
+        "RistrettoPoint(/* generator */ H<Sha512>)"
+    );
+}
+
+#[test]
+fn test_diff_values() {
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let good = &a * &b + &a;
+    let bad = &TestScalar::from(4u8).named("b") * &a + &a;
+
+    assert_eq!(
+        good.diff_values(&bad),
+        Some((hex(good.value.as_bytes()), hex(bad.value.as_bytes())))
+    );
+    assert_eq!(good.diff_values(&(&b * &a + &a)), None);
+    assert_eq!(good.diff_values(&(&a * &a)), None);
+}
+
+#[test]
+fn test_byte_diff() {
+    let a = TestScalar::from(0x1234u16).named("a");
+    let b = TestScalar::from(0x1334u16).named("b");
+
+    assert_eq!(a.byte_diff(&b), Some((1, 0x12, 0x13)));
+    assert_eq!(b.byte_diff(&a), Some((1, 0x13, 0x12)));
+    assert_eq!(a.byte_diff(&TestScalar::from(0x1234u16)), None);
+}
+
+#[test]
+fn test_functional_api() {
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+
+    assert!(add(&a, &b).strict_eq(&(&a + &b)));
+    assert!(sub(&a, &b).strict_eq(&(&a - &b)));
+    assert!(mul(&a, &b).strict_eq(&(&a * &b)));
+    assert!(neg(&a).strict_eq(&-&a));
+    assert!(invert(&a).strict_eq(&a.invert()));
+    assert!(add(&mul(&a, &b), &neg(&a)).strict_eq(&(&a * &b + -&a)));
+}
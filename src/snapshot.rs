@@ -0,0 +1,135 @@
+//! Snapshots of tracked values, for comparing computations across runs
+
+#[cfg(feature = "serde")]
+use std::{collections::HashMap, fs, io, path::Path};
+
+use crate::expr::Tree;
+
+/// The value and expression of a tracked scalar at some point in time
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    pub bytes: [u8; 32],
+    pub tree: Tree,
+}
+
+/// What changed between two snapshots
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    pub value_changed: bool,
+    pub tree_changed: bool,
+}
+
+impl SnapshotDiff {
+    /// Whether the snapshots are identical
+    pub fn is_empty(&self) -> bool {
+        !self.value_changed && !self.tree_changed
+    }
+}
+
+impl Snapshot {
+    /// Compares this snapshot with another one taken from a different run
+    pub fn diff(&self, other: &Snapshot) -> SnapshotDiff {
+        SnapshotDiff {
+            value_changed: self.bytes != other.bytes,
+            tree_changed: self.tree != other.tree,
+        }
+    }
+}
+
+/// Writes each snapshot as JSON to `<name>.json` in `dir`, creating the directory if needed
+#[cfg(feature = "serde")]
+pub fn save_snapshots(
+    dir: impl AsRef<Path>,
+    snapshots: &HashMap<String, Snapshot>,
+) -> io::Result<()> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+    for (name, snapshot) in snapshots {
+        let json = serde_json::to_string_pretty(snapshot)?;
+        fs::write(dir.join(format!("{name}.json")), json)?;
+    }
+    Ok(())
+}
+
+/// Reads back the snapshots saved with [`save_snapshots`], keyed by file name without the
+/// `.json` extension
+///
+/// Files with other extensions are ignored.
+#[cfg(feature = "serde")]
+pub fn load_snapshots(dir: impl AsRef<Path>) -> io::Result<HashMap<String, Snapshot>> {
+    let mut snapshots = HashMap::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            let snapshot = crate::from_json(&fs::read(&path)?)?;
+            snapshots.insert(name, snapshot);
+        }
+    }
+    Ok(snapshots)
+}
+
+#[test]
+fn test_value_changed_only() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let before = (&a + &b).snapshot();
+
+    let b = TestScalar::from(4u8).named("b");
+    let after = (&a + &b).snapshot();
+
+    assert_eq!(
+        before.diff(&after),
+        SnapshotDiff {
+            value_changed: true,
+            tree_changed: false,
+        }
+    );
+    assert!(before.diff(&before).is_empty());
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn test_save_and_load_snapshots() {
+    use crate::{
+        scalar::{Scalar, TestScalar},
+        Named,
+    };
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named_with_value("b");
+    let snapshots = HashMap::from([
+        ("sum".to_owned(), (&a + &b).snapshot()),
+        ("inverse".to_owned(), (&a * &b).invert().snapshot()),
+    ]);
+
+    let dir = std::env::temp_dir().join(format!("snapshots-{}", std::process::id()));
+    save_snapshots(&dir, &snapshots).unwrap();
+    let loaded = load_snapshots(&dir);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(loaded.unwrap(), snapshots);
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn test_load_deep_snapshot() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let mut x = TestScalar::from(1u8).named("x");
+    for _ in 0..500 {
+        x += &a;
+    }
+    let snapshots = HashMap::from([("deep".to_owned(), x.snapshot())]);
+
+    let dir = std::env::temp_dir().join(format!("deep-snapshots-{}", std::process::id()));
+    save_snapshots(&dir, &snapshots).unwrap();
+    let loaded = load_snapshots(&dir);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(loaded.unwrap(), snapshots);
+}
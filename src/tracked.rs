@@ -0,0 +1,515 @@
+//! A value paired with the expression tree that computed it
+
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+use core::{
+    hash::{Hash, Hasher},
+    mem,
+    ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
+
+use subtle::{Choice, ConstantTimeEq};
+
+use crate::{
+    auto_name, check_depth,
+    counter::{record, Op},
+    expr::Tree,
+    Named,
+};
+
+/// A value together with the [`Tree`] of operations that produced it
+///
+/// The operators act on the values as usual and record the operation in the tree. Equality,
+/// hashing and constant-time comparisons only look at the values.
+#[derive(Clone)]
+pub struct Tracked<T> {
+    pub(crate) value: T,
+    pub(crate) tree: Tree,
+}
+
+/// How the product of a value of this type by a `Rhs` is recorded
+///
+/// The default records a product of the operands in order. Points override it to record a
+/// scalar multiplication with the scalar first, e.g. both `z * P` and `P * z` are recorded as
+/// `z · P`.
+#[doc(hidden)]
+pub trait MulTree<Rhs> {
+    fn mul_tree(lhs: Tree, rhs: Tree) -> Tree {
+        Tree::product_of(lhs, rhs)
+    }
+}
+
+/// The canonical encoding of a value, as written to the transcript
+#[doc(hidden)]
+pub trait Encoding {
+    fn encoding(&self) -> [u8; 32];
+}
+
+/// Counts an operation, and appends it to the transcript of the current thread if there is one
+///
+/// Panics if the tree of the result is deeper than allowed by
+/// [`set_max_tree_depth`](crate::set_max_tree_depth).
+pub(crate) fn record_op<T: Encoding>(op: Op, operands: &[&Tree], result: &Tracked<T>) {
+    record_node(op, operands, &result.tree, || result.value.encoding());
+}
+
+/// Like [`record_op`], for an operation whose result is only computed, with `encoding`, when the
+/// transcript needs it
+pub(crate) fn record_node(
+    op: Op,
+    operands: &[&Tree],
+    tree: &Tree,
+    encoding: impl FnOnce() -> [u8; 32],
+) {
+    check_depth(tree);
+    record(op);
+    #[cfg(feature = "transcript")]
+    crate::transcript::append(op, operands, encoding);
+    #[cfg(not(feature = "transcript"))]
+    let _ = (operands, encoding);
+}
+
+/// Combines the items pairwise with `node`, so the tree of the result has logarithmic depth
+///
+/// The values are combined with `combine` in the same order, and each of the `n - 1` operations
+/// is recorded.
+pub(crate) fn balanced<T: Encoding>(
+    mut items: Vec<Tracked<T>>,
+    op: Op,
+    combine: fn(T, T) -> T,
+    node: fn(Box<Tree>, Box<Tree>) -> Tree,
+) -> Option<Tracked<T>> {
+    while items.len() > 1 {
+        let mut level = Vec::with_capacity(items.len().div_ceil(2));
+        let mut it = items.into_iter();
+        while let Some(l) = it.next() {
+            level.push(match it.next() {
+                Some(r) => {
+                    let result = Tracked {
+                        value: combine(l.value, r.value),
+                        tree: node(Box::new(l.tree), Box::new(r.tree)),
+                    };
+                    let (l, r) = result.tree.binary_operands().unwrap();
+                    record_op(op, &[l, r], &result);
+                    result
+                }
+                None => l,
+            });
+        }
+        items = level;
+    }
+    items.pop()
+}
+
+/// Whether the operations on this thread are logged to a transcript, which needs their operands
+pub(crate) fn transcript_active() -> bool {
+    #[cfg(feature = "transcript")]
+    return crate::transcript::is_active();
+    #[cfg(not(feature = "transcript"))]
+    return false;
+}
+
+impl<T: Encoding> Tracked<T> {
+    /// Applies an assign operator, moving the current tree under the new node instead of
+    /// cloning it, so that updating a value in a loop takes constant time per step
+    fn assign<U>(&mut self, op: Op, value: T, rhs: &Tracked<U>, node: fn(Tree, Tree) -> Tree) {
+        let lhs = mem::replace(&mut self.tree, Tree::Unnamed);
+        let logged = transcript_active().then(|| lhs.clone());
+        self.value = value;
+        self.tree = node(lhs, rhs.tree.clone());
+        record_op(
+            op,
+            &[logged.as_ref().unwrap_or(&Tree::Unnamed), &rhs.tree],
+            self,
+        );
+    }
+
+    /// Negates the value, moving the tree under the new node instead of cloning it
+    ///
+    /// Both `-&x` and the owned `-x` from `define_neg_variants!` go through this, so they record
+    /// the same node.
+    pub(crate) fn negate(self) -> Self
+    where
+        T: Neg<Output = T>,
+    {
+        let negation = Tracked {
+            value: self.value.neg(),
+            tree: Tree::Neg(Box::new(self.tree)),
+        };
+        let operand = negation.tree.unary_operand().unwrap();
+        record_op(Op::Neg, &[operand], &negation);
+        negation
+    }
+}
+
+impl<T> Tracked<T> {
+    /// Converts a value like [`From`], labeling it with a name right away
+    pub fn from_named<S>(value: T, name: S) -> Self
+    where
+        String: From<S>,
+    {
+        Self {
+            value,
+            tree: Tree::Name(name.into()),
+        }
+    }
+
+    /// Names this value like [`Named::named`], but keeps its expression under the name, to be
+    /// shown by [`Tree::inline_names`]
+    pub fn named_keeping<S>(self, name: S) -> Self
+    where
+        String: From<S>,
+    {
+        Tracked {
+            tree: Tree::Named(name.into(), Box::new(self.tree)),
+            ..self
+        }
+    }
+}
+
+impl<T: PartialEq> Tracked<T> {
+    /// Whether both the values and the trees are equal, unlike `==` which only compares the
+    /// values
+    pub fn strict_eq(&self, other: &Self) -> bool {
+        self.value == other.value && self.tree == other.tree
+    }
+}
+
+impl<T> From<T> for Tracked<T> {
+    /// Tracks a value as an unnamed leaf, or under an automatic name if enabled by
+    /// [`set_auto_names`](crate::set_auto_names)
+    fn from(value: T) -> Self {
+        Self {
+            value,
+            tree: auto_name().map_or(Tree::Unnamed, Tree::Name),
+        }
+    }
+}
+
+impl<T: PartialEq> PartialEq for Tracked<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq> Eq for Tracked<T> {}
+
+impl<T: Hash> Hash for Tracked<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state);
+    }
+}
+
+impl<T: ConstantTimeEq> ConstantTimeEq for Tracked<T> {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.value.ct_eq(&other.value)
+    }
+}
+
+impl<T> Named for Tracked<T> {
+    fn named<S>(self, name: S) -> Self
+    where
+        String: From<S>,
+    {
+        Tracked {
+            tree: Tree::Name(name.into()),
+            ..self
+        }
+    }
+}
+
+impl<'b, T> AddAssign<&'b Tracked<T>> for Tracked<T>
+where
+    T: Copy + Add<Output = T> + Encoding,
+{
+    fn add_assign(&mut self, rhs: &'b Tracked<T>) {
+        self.assign(Op::Add, self.value + rhs.value, rhs, Tree::sum_of);
+    }
+}
+
+impl<'b, T> Add<&'b Tracked<T>> for &Tracked<T>
+where
+    T: Copy + Add<Output = T> + Encoding,
+{
+    type Output = Tracked<T>;
+
+    fn add(self, rhs: &'b Tracked<T>) -> Self::Output {
+        let sum = Tracked {
+            value: self.value + rhs.value,
+            tree: Tree::sum_of(self.tree.clone(), rhs.tree.clone()),
+        };
+        record_op(Op::Add, &[&self.tree, &rhs.tree], &sum);
+        sum
+    }
+}
+
+impl<'b, T> SubAssign<&'b Tracked<T>> for Tracked<T>
+where
+    T: Copy + Sub<Output = T> + Encoding,
+{
+    fn sub_assign(&mut self, rhs: &'b Tracked<T>) {
+        self.assign(Op::Sub, self.value - rhs.value, rhs, |l, r| {
+            Tree::Sub(Box::new(l), Box::new(r))
+        });
+    }
+}
+
+impl<'b, T> Sub<&'b Tracked<T>> for &Tracked<T>
+where
+    T: Copy + Sub<Output = T> + Encoding,
+{
+    type Output = Tracked<T>;
+
+    fn sub(self, rhs: &'b Tracked<T>) -> Self::Output {
+        let difference = Tracked {
+            value: self.value - rhs.value,
+            tree: Tree::Sub(Box::new(self.tree.clone()), Box::new(rhs.tree.clone())),
+        };
+        record_op(Op::Sub, &[&self.tree, &rhs.tree], &difference);
+        difference
+    }
+}
+
+impl<'b, T, U> MulAssign<&'b Tracked<U>> for Tracked<T>
+where
+    T: Copy + Mul<U, Output = T> + MulTree<U> + Encoding,
+    U: Copy,
+{
+    fn mul_assign(&mut self, rhs: &'b Tracked<U>) {
+        self.assign(Op::Mul, self.value * rhs.value, rhs, T::mul_tree);
+    }
+}
+
+impl<'b, T, U> Mul<&'b Tracked<U>> for &Tracked<T>
+where
+    T: Copy + Mul<U> + MulTree<U>,
+    T::Output: Encoding,
+    U: Copy,
+{
+    type Output = Tracked<T::Output>;
+
+    fn mul(self, rhs: &'b Tracked<U>) -> Self::Output {
+        let product = Tracked {
+            value: self.value * rhs.value,
+            tree: T::mul_tree(self.tree.clone(), rhs.tree.clone()),
+        };
+        record_op(Op::Mul, &[&self.tree, &rhs.tree], &product);
+        product
+    }
+}
+
+impl<T> Neg for &Tracked<T>
+where
+    T: Copy + Neg<Output = T> + Encoding,
+{
+    type Output = Tracked<T>;
+
+    fn neg(self) -> Self::Output {
+        self.clone().negate()
+    }
+}
+
+/// Names the elements of a slice `base[0]`, `base[1]`, …
+pub fn name_indexed<T>(slice: &mut [Tracked<T>], base: &str) {
+    for (i, x) in slice.iter_mut().enumerate() {
+        x.tree = Tree::Name(format!("{base}[{i}]"));
+    }
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_equivalent<T: PartialEq>(left: &Tracked<T>, right: &Tracked<T>) {
+    let (l, r) = (left.tree.canonicalize(), right.tree.canonicalize());
+    if let Some((ld, rd)) = l.first_difference(&r) {
+        panic!(
+            "assertion `left ≡ right` failed: expressions differ\n  left: {l:?}\n right: {r:?}\n first difference: {ld:?} vs {rd:?}"
+        );
+    }
+    if left.value != right.value {
+        panic!("assertion `left ≡ right` failed: values differ\n expression: {l:?}");
+    }
+}
+
+#[test]
+fn test_aliases() {
+    use crate::{
+        ristretto::{RistrettoPoint, TestRistrettoPoint},
+        scalar::TestScalar,
+    };
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&a).named("P");
+    let q = TestRistrettoPoint::mul_base(&b).named("Q");
+
+    let mut s = &a * &b - -&a;
+    s += &b;
+    s *= &a;
+    let mut r = &s * &p - &q;
+    r -= -(&q * &a);
+    r *= &b;
+
+    assert_eq!(s.value, (a.value * b.value + a.value + b.value) * a.value);
+    assert_eq!(
+        r.value,
+        ((s.value * p.value) - q.value + q.value * a.value) * b.value
+    );
+    assert_eq!(format!("{s:?}"), "Scalar(((a * b - -a) + b) * a)");
+    assert_eq!(
+        format!("{r:?}"),
+        "RistrettoPoint(b · ((((a * b - -a) + b) * a · P - Q) - -a · Q))"
+    );
+}
+
+#[test]
+fn test_assert_equivalent() {
+    use crate::scalar::TestScalar;
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+
+    assert_equivalent!(&a * &b + &a, &a + &b * &a);
+}
+
+#[test]
+#[should_panic(expected = "expressions differ")]
+fn test_assert_equivalent_different_trees() {
+    use crate::scalar::TestScalar;
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let c = TestScalar::from(6u8).named("c");
+
+    assert_equivalent!(&a * &b, c);
+}
+
+#[test]
+fn test_from_named() {
+    use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
+
+    use crate::{ristretto::TestRistrettoPoint, scalar::TestScalar};
+
+    let d = Scalar::from(5u8);
+    let k = TestScalar::from_named(d, "k");
+    let p = TestRistrettoPoint::from_named(RistrettoPoint::mul_base(&d), "P");
+
+    assert_eq!(k, TestScalar::from(d));
+    assert_eq!(format!("{k:?}"), "Scalar(k)");
+    assert_eq!(format!("{p:?}"), "RistrettoPoint(P)");
+}
+
+#[test]
+fn test_strict_eq() {
+    use crate::scalar::TestScalar;
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+
+    assert_eq!(&a * &b, &b * &a);
+    assert!(!(&a * &b).strict_eq(&(&b * &a)));
+    assert!((&a * &b).strict_eq(&(&a * &b)));
+    assert!(!a.strict_eq(&b));
+}
+
+#[test]
+fn test_name_indexed() {
+    use crate::{
+        ristretto::{RistrettoPoint, TestRistrettoPoint},
+        scalar::TestScalar,
+    };
+
+    let mut generators: Vec<_> = (1..=3u8)
+        .map(|i| TestRistrettoPoint::mul_base(&TestScalar::from(i)))
+        .collect();
+    name_indexed(&mut generators, "G");
+
+    assert_eq!(
+        generators
+            .iter()
+            .map(|g| format!("{g:?}"))
+            .collect::<Vec<_>>(),
+        [
+            "RistrettoPoint(G[0])",
+            "RistrettoPoint(G[1])",
+            "RistrettoPoint(G[2])"
+        ]
+    );
+}
+
+#[test]
+fn test_neg_variants() {
+    use crate::{
+        ristretto::{RistrettoPoint, TestRistrettoPoint},
+        scalar::TestScalar,
+        Named,
+    };
+
+    let x = TestScalar::from(2u8).named("x");
+    let p = TestRistrettoPoint::mul_base(&x).named("P");
+
+    assert!((-&x).strict_eq(&-x.clone()));
+    assert_eq!(format!("{:?}", -x.clone()), "Scalar(-x)");
+    assert_eq!(-&x + &x, TestScalar::from(0u8));
+    assert!((-&p).strict_eq(&-p.clone()));
+    assert_eq!(format!("{:?}", -p.clone()), "RistrettoPoint(-P)");
+    assert_eq!(-&p + &p, TestRistrettoPoint::default());
+}
+
+#[test]
+#[cfg(feature = "std")]
+#[should_panic(expected = "exceeds the maximum tree depth of 3")]
+fn test_max_tree_depth() {
+    use crate::{scalar::TestScalar, set_max_tree_depth, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    set_max_tree_depth(Some(3));
+    let x = &(&a + &a) * &a;
+    assert_eq!(x.tree.depth(), 3);
+    let _ = &x + &a;
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_auto_names() {
+    use curve25519_dalek::scalar::Scalar;
+
+    use crate::{scalar::TestScalar, set_auto_names};
+
+    set_auto_names(true);
+    let a = TestScalar::from(Scalar::from(2u8));
+    let b = TestScalar::from(Scalar::from(3u8));
+    set_auto_names(false);
+    let c = TestScalar::from(Scalar::from(5u8));
+    set_auto_names(true);
+    let d = TestScalar::from(Scalar::from(7u8));
+    set_auto_names(false);
+
+    assert_eq!(format!("{:?}", &a * &b + &c), "Scalar((v0 * v1 + ?))");
+    assert_eq!(format!("{d:?}"), "Scalar(v0)");
+}
+
+#[test]
+fn test_assign_moves_tree() {
+    use crate::{
+        ristretto::{RistrettoPoint, TestRistrettoPoint},
+        scalar::TestScalar,
+    };
+
+    const STEPS: usize = 10_000;
+
+    let p = TestRistrettoPoint::mul_base(&TestScalar::from(2u8)).named("P");
+    let q = TestRistrettoPoint::mul_base(&TestScalar::from(3u8)).named("Q");
+    let mut acc = &p + &q;
+    let first: *const Tree = acc.tree.binary_operands().unwrap().0;
+    for _ in 0..STEPS {
+        acc += &q;
+    }
+
+    // the first sum was moved down the tree, never copied
+    let mut innermost = &acc.tree;
+    for _ in 0..STEPS {
+        innermost = innermost.binary_operands().unwrap().0;
+    }
+    let innermost = innermost.binary_operands().unwrap().0;
+    assert!(core::ptr::eq(innermost, first));
+    assert_eq!(acc, &p + &(&TestScalar::from(STEPS as u64 + 1) * &q));
+}
@@ -0,0 +1,199 @@
+//! Logging of every operation on tracked values, as JSON lines for offline analysis
+//!
+//! Like the strict mode and the rendering options, the transcript belongs to the current thread:
+//! only the operations performed on the thread that installed a writer are logged.
+//!
+//! Each entry holds the whole trees of its operands, so a long chain of dependent operations
+//! writes a transcript whose size grows quadratically with the length of the chain. Naming
+//! intermediate values with [`Named::named`](crate::Named::named) cuts their trees short.
+
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, BufRead, BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{counter::Op, expr::Tree, hex};
+
+/// One operation of a transcript
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    pub op: Op,
+    pub operands: Vec<Tree>,
+    /// The canonical encoding of the result, in hexadecimal
+    pub result: String,
+}
+
+/// Destination of a transcript, writing one [`TranscriptEntry`] per line
+pub struct TranscriptWriter {
+    out: Box<dyn Write>,
+}
+
+impl TranscriptWriter {
+    pub fn new(out: impl Write + 'static) -> Self {
+        Self { out: Box::new(out) }
+    }
+
+    /// Writes the transcript to a new file, replacing any existing one
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+
+    fn append(&mut self, entry: &impl Serialize) -> io::Result<()> {
+        serde_json::to_writer(&mut self.out, entry)?;
+        self.out.write_all(b"\n")
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+thread_local! {
+    static TRANSCRIPT: RefCell<Option<TranscriptWriter>> = const { RefCell::new(None) };
+}
+
+/// Makes the operations on the current thread append to `writer`, or stops logging them with
+/// `None`
+///
+/// Returns the writer that was installed before, so it can be flushed or restored.
+pub fn set_transcript(writer: Option<TranscriptWriter>) -> Option<TranscriptWriter> {
+    TRANSCRIPT.with(|t| t.replace(writer))
+}
+
+/// Whether the operations on the current thread are being logged
+pub(crate) fn is_active() -> bool {
+    TRANSCRIPT.with(|t| t.borrow().is_some())
+}
+
+/// Reads back a transcript written by a [`TranscriptWriter`]
+pub fn read_transcript(reader: impl BufRead) -> io::Result<Vec<TranscriptEntry>> {
+    reader
+        .lines()
+        .map(|line| Ok(crate::from_json(line?.as_bytes())?))
+        .collect()
+}
+
+/// A [`TranscriptEntry`] borrowing its operands, serialized the same way
+#[derive(Serialize)]
+struct EntryRef<'a> {
+    op: Op,
+    operands: &'a [&'a Tree],
+    result: String,
+}
+
+/// Appends an operation to the transcript of the current thread, if there is one
+///
+/// The encoding of the result is only computed when there is a transcript to write it to.
+///
+/// # Panics
+///
+/// If the transcript can't be written, since the operators have no way to report it.
+pub(crate) fn append(op: Op, operands: &[&Tree], result: impl FnOnce() -> [u8; 32]) {
+    TRANSCRIPT.with(|t| {
+        if let Some(writer) = t.borrow_mut().as_mut() {
+            let entry = EntryRef {
+                op,
+                operands,
+                result: hex(&result()),
+            };
+            writer
+                .append(&entry)
+                .expect("failed to write the operation transcript");
+        }
+    });
+}
+
+#[test]
+fn test_transcript() {
+    use crate::{
+        ristretto::{RistrettoPoint, TestRistrettoPoint},
+        scalar::{Scalar, TestScalar},
+        Named,
+    };
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&a).named("P");
+
+    let path = std::env::temp_dir().join(format!("transcript-{}.jsonl", std::process::id()));
+    set_transcript(Some(TranscriptWriter::create(&path).unwrap()));
+    let s = &a + &b;
+    let q = &s.invert() * &p;
+    let mut t = s.clone();
+    t += &a;
+    set_transcript(None).unwrap().flush().unwrap();
+    let _ = &q - &p;
+
+    let entries = read_transcript(io::BufReader::new(File::open(&path).unwrap()));
+    std::fs::remove_file(&path).unwrap();
+    let entries = entries.unwrap();
+
+    let ops: Vec<_> = entries.iter().map(|e| e.op).collect();
+    assert_eq!(ops, [Op::Add, Op::Invert, Op::Mul, Op::Add]);
+    assert_eq!(entries[0].operands, [a.tree.clone(), b.tree]);
+    assert_eq!(entries[0].result, hex(&s.to_bytes()));
+    assert_eq!(entries[2].operands[1], p.tree);
+    assert_eq!(entries[2].result, hex(q.value.compress().as_bytes()));
+    assert_eq!(entries[3].operands, [s.tree, a.tree]);
+    assert_eq!(entries[3].result, hex(&t.to_bytes()));
+}
+
+#[test]
+fn test_transcript_multiscalar_mul() {
+    use crate::{
+        ristretto::{RistrettoPoint, TestRistrettoPoint},
+        scalar::TestScalar,
+        Named,
+    };
+
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&a).named("P");
+    let q = TestRistrettoPoint::mul_base(&b).named("Q");
+
+    let path = std::env::temp_dir().join(format!("multiscalar-{}.jsonl", std::process::id()));
+    set_transcript(Some(TranscriptWriter::create(&path).unwrap()));
+    let r = TestRistrettoPoint::multiscalar_mul([&a, &b], [&p, &q]);
+    set_transcript(None).unwrap().flush().unwrap();
+
+    let entries = read_transcript(io::BufReader::new(File::open(&path).unwrap()));
+    std::fs::remove_file(&path).unwrap();
+    let entries = entries.unwrap();
+
+    let ops: Vec<_> = entries.iter().map(|e| e.op).collect();
+    assert_eq!(ops, [Op::Mul, Op::Mul, Op::Add]);
+    assert_eq!(entries[1].operands, [b.tree.clone(), q.tree.clone()]);
+    assert_eq!(
+        entries[1].result,
+        hex((&b * &q).value.compress().as_bytes())
+    );
+    assert_eq!(entries[2].result, hex(r.value.compress().as_bytes()));
+}
+
+#[test]
+fn test_transcript_deep_tree() {
+    use crate::{scalar::TestScalar, Named};
+
+    let a = TestScalar::from(2u8).named("a");
+    let mut x = TestScalar::from(1u8).named("x");
+    for _ in 0..500 {
+        x += &a;
+    }
+
+    let path = std::env::temp_dir().join(format!("deep-{}.jsonl", std::process::id()));
+    set_transcript(Some(TranscriptWriter::create(&path).unwrap()));
+    let y = &x * &a;
+    set_transcript(None).unwrap().flush().unwrap();
+
+    let entries = read_transcript(io::BufReader::new(File::open(&path).unwrap()));
+    std::fs::remove_file(&path).unwrap();
+    let entries = entries.unwrap();
+
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].operands, [x.tree, a.tree]);
+    assert_eq!(entries[0].result, hex(&y.value.to_bytes()));
+}
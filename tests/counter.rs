@@ -0,0 +1,259 @@
+//! The operation counter is global, so these tests run in their own process and are serialized
+
+use std::{
+    sync::{Mutex, MutexGuard},
+    thread,
+};
+
+use curve25519_dalek_dbg::{
+    counter::{OpCounter, OpCounts},
+    edwards::TestEdwardsPoint,
+    ristretto::{RistrettoPoint, TestRistrettoBasepointTable, TestRistrettoPoint},
+    scalar::TestScalar,
+    Named,
+};
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+fn lock() -> MutexGuard<'static, ()> {
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[test]
+fn test_nested_scopes() {
+    let _lock = lock();
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+
+    let outer = OpCounter::scope();
+    let c = &a + &b;
+    let inner_delta = {
+        let inner = OpCounter::scope();
+        let _ = &c * &a;
+        let _ = -&c;
+        inner.delta()
+    };
+    let _ = &c - &b;
+
+    assert_eq!(
+        inner_delta,
+        OpCounts {
+            mul: 1,
+            neg: 1,
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        outer.delta(),
+        OpCounts {
+            add: 1,
+            sub: 1,
+            mul: 1,
+            neg: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_threads_accumulate() {
+    let _lock = lock();
+    const THREADS: u64 = 8;
+    const OPS: u64 = 100;
+
+    OpCounter::reset();
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            thread::spawn(|| {
+                let mut x = TestScalar::from(1u8).named("x");
+                let y = TestScalar::from(2u8).named("y");
+                for _ in 0..OPS {
+                    x += &y;
+                }
+            })
+        })
+        .collect();
+    handles.into_iter().for_each(|h| h.join().unwrap());
+
+    assert_eq!(
+        OpCounter::snapshot(),
+        OpCounts {
+            add: THREADS * OPS,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_pause() {
+    let _lock = lock();
+    let a = TestScalar::from(2u8).named("a");
+
+    let scope = OpCounter::scope();
+    let b = {
+        let _pause = OpCounter::pause();
+        let b = &a * &a;
+        let _nested = OpCounter::pause();
+        -&b
+    };
+    let _ = &a + &b;
+
+    assert_eq!(
+        scope.delta(),
+        OpCounts {
+            add: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_pause_is_per_thread() {
+    let _lock = lock();
+    let a = TestScalar::from(2u8).named("a");
+
+    let scope = OpCounter::scope();
+    let _pause = OpCounter::pause();
+    thread::scope(|s| {
+        s.spawn(|| &a * &a);
+    });
+    let _ = &a + &a;
+
+    assert_eq!(
+        scope.delta(),
+        OpCounts {
+            mul: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+#[cfg(not(feature = "std"))]
+fn test_pause_is_global() {
+    let _lock = lock();
+    let a = TestScalar::from(2u8).named("a");
+
+    let scope = OpCounter::scope();
+    let _pause = OpCounter::pause();
+    thread::scope(|s| {
+        s.spawn(|| &a * &a);
+    });
+    let _ = &a + &a;
+
+    assert_eq!(scope.delta(), OpCounts::default());
+}
+
+#[test]
+fn test_sum_and_product() {
+    let _lock = lock();
+    let xs: Vec<_> = (1..=5u8).map(TestScalar::from).collect();
+
+    let scope = OpCounter::scope();
+    let _: TestScalar = xs.iter().sum();
+    let _: TestScalar = xs[..3].iter().product();
+    let _: TestScalar = xs[..1].iter().product();
+
+    assert_eq!(
+        scope.delta(),
+        OpCounts {
+            add: 4,
+            mul: 2,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_multiscalar_mul() {
+    let _lock = lock();
+    let scalars: Vec<_> = (1..=3u8).map(TestScalar::from).collect();
+    let points: Vec<_> = scalars.iter().map(TestRistrettoPoint::mul_base).collect();
+
+    let scope = OpCounter::scope();
+    let _ = TestRistrettoPoint::multiscalar_mul(&scalars, &points);
+
+    assert_eq!(
+        scope.delta(),
+        OpCounts {
+            add: 2,
+            mul: 3,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_mul_base() {
+    let _lock = lock();
+    let a = TestScalar::from(2u8).named("a");
+    let table = TestRistrettoBasepointTable::create(&TestRistrettoPoint::mul_base(&a));
+
+    let scope = OpCounter::scope();
+    let _ = TestRistrettoPoint::mul_base(&a);
+    let _ = table.mul_base(&a);
+    let _ = &table * &a;
+    let _ = table.basepoint();
+
+    assert_eq!(
+        scope.delta(),
+        OpCounts {
+            mul: 3,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_vartime_double_scalar_mul_basepoint() {
+    let _lock = lock();
+    let a = TestScalar::from(2u8).named("a");
+    let b = TestScalar::from(3u8).named("b");
+    let p = TestRistrettoPoint::mul_base(&a).named("P");
+
+    let scope = OpCounter::scope();
+    let _ = TestRistrettoPoint::vartime_double_scalar_mul_basepoint(&a, &p, &b);
+
+    assert_eq!(
+        scope.delta(),
+        OpCounts {
+            add: 1,
+            mul: 2,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_mul_base_clamped() {
+    let _lock = lock();
+
+    let scope = OpCounter::scope();
+    let _ = TestRistrettoPoint::mul_base_clamped([7; 32]);
+
+    assert_eq!(
+        scope.delta(),
+        OpCounts {
+            mul: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_edwards_mul_base() {
+    let _lock = lock();
+    let a = TestScalar::from(2u8).named("a");
+
+    let scope = OpCounter::scope();
+    let _ = TestEdwardsPoint::mul_base(&a);
+
+    assert_eq!(
+        scope.delta(),
+        OpCounts {
+            mul: 1,
+            ..Default::default()
+        }
+    );
+}